@@ -85,23 +85,277 @@ const on_save = async (score) => {
     again_style.transform = 'translate(50%, 50%)'
 }
 
-window.scored       = (score) => overlay().innerHTML = `Score: ${score}`
+let current_level = { name: '', level: 0, levels: 0, target: undefined }
+
+window.level_started = (name, level, levels, target) => current_level = { name, level, levels, target }
+
+// The score, the rest of the HUD and the pause text are drawn in the canvas.
 window.game_over    = (score) => name_input(score)
-window.pause        = () => overlay().innerText = 'PAUSED'
 window.clear_screen = () => overlay().innerText = ''
 
+window.level_complete = (score) => overlay().innerHTML = `
+    Level complete with ${score} apples!
+    <h2>${current_level.levels > 0 ? `Next up: ${current_level.name}` : 'Press space to play again.'}</h2>
+    ${current_level.levels > 0 ? `Level ${current_level.level}/${current_level.levels}. Press space to start.` : ''}`
+
+window.campaign_complete = (score) => overlay().innerHTML = `
+    You beat the campaign, finishing with ${score} apples!
+    <h2>Press space to play again.</h2>`
+
+let versus_scores = [0, 0]
+
+window.player_scored = (player, score) => versus_scores[player] = score
+
+window.versus_over = (winner) => {
+    overlay().innerHTML = `
+        ${winner === undefined ? "It's a draw!" : `Player ${winner + 1} wins!`}
+        <br>
+        P1: ${versus_scores[0]} — P2: ${versus_scores[1]}
+        <h2>Press space to play again.</h2>`
+    versus_scores = [0, 0]
+}
+
+window.net_rematch = () => overlay().innerHTML = 'Waiting for the other player to want a rematch...'
+
+window.net_desync = (tick) => {
+    console.error(`Online game out of sync since tick ${tick}`)
+    overlay().innerHTML = `The games went out of sync on tick ${tick}.`
+}
+
+const start_broadcast = (m, url) => {
+    const socket = new WebSocket(`${url}/broadcast`)
+
+    window.broadcast = (frame) => {
+        if (socket.readyState === WebSocket.OPEN) socket.send(frame)
+    }
+
+    // The first keyframe is sent again once connected, so nobody misses the start.
+    socket.onopen  = () => m.broadcast_start()
+    socket.onclose = () => m.broadcast_stop()
+}
+
+const start_spectating = (m, url) => {
+    m.spectate()
+
+    const socket = new WebSocket(`${url}/watch`)
+    overlay().innerHTML = 'Waiting for the game to start...'
+
+    socket.onmessage = ({ data }) => {
+        m.spectator_receive(data)
+    }
+    socket.onclose = () => overlay().innerHTML = 'The game is no longer being streamed.'
+}
+
+const connect_online = (m, url, level) => {
+    const socket = new WebSocket(`${url}?level=${level}`)
+
+    window.net_send = (message) => {
+        if (socket.readyState === WebSocket.OPEN) socket.send(message)
+    }
+
+    overlay().innerHTML = 'Waiting for another player...'
+
+    socket.onmessage = ({ data }) => {
+        if (data.startsWith('join')) {
+            const player = Number(data.split(' ')[1])
+            versus_scores = [0, 0]
+            overlay().innerHTML = `You are player ${player + 1} (${player === 0 ? 'green' : 'purple'}).`
+        }
+        if (data === 'rematch') {
+            overlay().innerHTML = `The other player wants a rematch.<h2>Press space to play again.</h2>`
+        }
+        if (!m.net_receive(data)) console.error(`Unreadable message from the relay: ${data}`)
+    }
+
+    socket.onclose = () => {
+        m.net_leave()
+        overlay().innerHTML = 'Disconnected from the other player.'
+    }
+}
+
+// The graphics card was taken away, usually by a driver reset. The game waits for it.
+let before_lost = ''
+
+window.renderer_lost = () => {
+    before_lost = overlay().innerHTML
+    overlay().innerHTML = 'Lost the graphics, waiting for them to come back...'
+}
+window.renderer_restored = () => overlay().innerHTML = before_lost
+
+window.apple_expired = () => {
+    const previous = overlay().innerHTML
+    overlay().innerHTML = 'Too slow!'
+    setTimeout(() => {
+        if (overlay().innerHTML === 'Too slow!') overlay().innerHTML = previous
+    }, 600)
+}
+
 rust.then(m => {
     window.addEventListener('keydown', m.key_press_event)
 
+    // webgl2, or canvas2d where the browser has no WebGL 2.
+    window.renderer_backend = m.renderer_backend()
+    document.body.dataset.renderer = window.renderer_backend
+    console.info(`Drawing with ${window.renderer_backend}`)
+
     overlay().innerHTML = `
         Can you solve
         <h2>the_snack_case!?!?!</h2>
         
         Press space to start or pause the game.
         <br>
-        Control the snek using WASD or arrow keys.`
+        Control the snek using WASD or arrow keys.
+        <br>
+        Press P to let the bot play.`
+
+    window.load_level_from_url = async (url) => {
+        const response = await fetch(url)
+        return m.load_level_from_string(await response.text())
+    }
+
+    // Shaders for skins, see `load_shaders` in src/lib.rs.
+    window.load_shaders_from_url = async (vertex_url, fragment_url) => {
+        const [vertex, fragment] = await Promise.all([vertex_url, fragment_url].map(async url => (await fetch(url)).text()))
+        return m.load_shaders(vertex, fragment)
+    }
+
+    // On the container, since the canvas is swapped for a new one when WebGL stops working.
+    // Events from anything but the canvas don't hit a cell, see `mouse_cell`.
+    const container = document.querySelector('#container')
+    container.addEventListener('mousedown', m.editor_mouse_down)
+    container.addEventListener('mousemove', m.editor_mouse_move)
+    window.addEventListener('mouseup', m.editor_mouse_up)
+
+    const params = new URLSearchParams(window.location.search)
+    if (params.has('editor')) open_editor(m)
+    if (params.has('generate')) {
+        const seed = Number(params.get('seed') ?? Math.floor(Math.random() * 2 ** 32))
+        m.load_generated_level(params.get('generate'), seed)
+    }
+    if (params.has('campaign') && !m.start_campaign(params.get('campaign') === 'restart')) {
+        overlay().innerHTML = `
+            You already beat the campaign!
+            <h2><a href="?campaign=restart">Play it again from the start</a></h2>`
+    }
+    const relay = `ws://${window.location.hostname}:8081`
+    if (params.has('broadcast')) start_broadcast(m, params.get('broadcast') || relay)
+    if (params.has('spectate')) start_spectating(m, params.get('spectate') || relay)
+    if (params.has('online')) connect_online(m, params.get('online') || relay, params.get('level') ?? 0)
+
+    // ?effects=crt,bloom,vignette,chromatic-aberration
+    for (const effect of (params.get('effects') ?? '').split(',').filter(effect => effect)) {
+        if (!m.set_effect(effect, true)) console.error(`Unknown effect ${effect}`)
+    }
+
+    // P hands the snake over to the bot and back.
+    let autopilot = params.has('autopilot')
+    m.set_autopilot(autopilot)
+    window.addEventListener('keydown', e => {
+        if (e.key !== 'p' || e.target instanceof HTMLInputElement) return
+        autopilot = !autopilot
+        m.set_autopilot(autopilot)
+    })
+
+    if (params.has('versus')) {
+        m.set_players(2)
+        overlay().innerHTML = `
+            Two players, one keyboard.
+            <br>
+            Player 1 (green) uses WASD, player 2 (purple) the arrow keys.
+            <h2>Press space to start.</h2>`
+    }
+
+    // ?shaders=skin.vert,skin.frag
+    const shaders = params.get('shaders')
+    if (shaders) load_shaders_from_url(...shaders.split(',')).catch(e => {
+        console.error(`Shaders not loaded, ${e.stage}${e.line === undefined ? '' : `, line ${e.line}`}: ${e.message ?? e}`)
+    })
+
+    // ?level=2 picks a built-in level, anything else is fetched as a level file.
+    const level = new URLSearchParams(window.location.search).get('level')
+    if (level && !isNaN(level)) m.load_level(Number(level))
+    else if (level) load_level_from_url(level).catch(e => {
+        console.error(`Level ${level}, line ${e.line}, column ${e.column}: ${e.message}`)
+        overlay().innerText = `Broken level: ${e.message} (line ${e.line}, column ${e.column})`
+    })
 
     m.start()
 }).catch(console.error);
 
 const overlay = () => document.querySelector('#overlay')
+
+const open_editor = (m) => {
+    m.editor_open()
+
+    overlay().style.pointerEvents = 'none'
+    overlay().innerText = ''
+
+    const toolbar = document.querySelector('#editor-toolbar')
+    const directions = ['up', 'down', 'left', 'right']
+    const brushes    = [
+        'wall', 'portal', 'start', 'apple', 'erase', 'ice', 'mud',
+        ...directions.map(d => `conveyor-${d}`),
+        ...directions.map(d => `gate-${d}`),
+    ]
+
+    toolbar.innerHTML = `
+        ${brushes.map(b => `<button data-brush="${b}">${b}</button>`).join('')}
+        <button id="editor-undo">undo</button>
+        <button id="editor-redo">redo</button>
+        <label>name <input id="editor-name" type="text"></label>
+        <label><input id="editor-wrap" type="checkbox" checked> wrap</label>
+        <label>speed <input id="editor-speed" type="number" value="220" min="50" max="1000"></label>
+        <label>target <input id="editor-target" type="number" value="0" min="0"></label>
+        <button id="editor-playtest">playtest</button>
+        <button id="editor-export">export</button>
+        <button id="editor-close">play</button>
+        <div>Click the start again to turn it. Esc stops the playtest.</div>
+        <textarea id="editor-output" rows="12" cols="20" readonly></textarea>`
+
+    toolbar.querySelectorAll('[data-brush]').forEach(button =>
+        button.onclick = () => m.editor_set_brush(button.dataset.brush))
+
+    document.querySelector('#editor-undo').onclick = () => m.editor_undo()
+    document.querySelector('#editor-redo').onclick = () => m.editor_redo()
+
+    document.querySelector('#editor-name').value = m.editor_name()
+
+    const update_rules = () => m.editor_set_rules(
+        document.querySelector('#editor-name').value,
+        document.querySelector('#editor-wrap').checked,
+        Number(document.querySelector('#editor-speed').value),
+        Number(document.querySelector('#editor-target').value)
+    )
+    toolbar.querySelectorAll('input').forEach(input => input.onchange = update_rules)
+
+    const broken_level = (error) => {
+        console.error(`Edited level, line ${error.line}, column ${error.column}: ${error.message}`)
+        overlay().innerText = `Broken level: ${error.message} (line ${error.line}, column ${error.column})`
+    }
+
+    document.querySelector('#editor-playtest').onclick = e => {
+        e.currentTarget.blur()
+        try {
+            m.editor_playtest(true)
+            overlay().innerText = 'Playtesting. Press space to start.'
+        } catch (error) {
+            broken_level(error)
+        }
+    }
+
+    // Leaves the editor and plays the edited level like any other.
+    document.querySelector('#editor-close').onclick = e => {
+        e.currentTarget.blur()
+        try {
+            m.editor_close()
+            toolbar.innerHTML = ''
+            overlay().style.pointerEvents = ''
+            overlay().innerText = 'Press space to start.'
+        } catch (error) {
+            broken_level(error)
+        }
+    }
+
+    document.querySelector('#editor-export').onclick = () =>
+        document.querySelector('#editor-output').value = m.editor_export()
+}
@@ -0,0 +1,91 @@
+// Pairs up players for online versus games and passes their messages along.
+// Run with `npm run relay`, then open the game with `?online` in two browsers.
+//
+// Also streams a game to spectators: the page opened with `?broadcast`
+// sends its frames to `/broadcast`, and every page opened with `?spectate`
+// gets them from `/watch`.
+const { WebSocketServer } = require('ws')
+
+const port = process.env.RELAY_PORT || 8081
+const relay = new WebSocketServer({ port })
+
+let waiting = null
+
+const watchers = new Set()
+let last_keyframe = null
+
+const versus = (socket, params) => {
+    socket.level = Number(params.get('level') ?? 0)
+
+    if (!waiting || waiting.readyState !== waiting.OPEN) {
+        waiting = socket
+        return
+    }
+
+    const players = [waiting, socket]
+    waiting = null
+
+    players.forEach((player, i) => {
+        const other = players[1 - i]
+
+        player.on('message', data => {
+            const message = data.toString()
+
+            // Once both players asked for a rematch they get a new game.
+            if (message === 'rematch') {
+                player.rematch = true
+                if (other.rematch) return start(players)
+            }
+
+            if (other.readyState === other.OPEN) other.send(message)
+        })
+        player.on('close', () => other.close())
+    })
+
+    start(players)
+}
+
+// Both games start from the same seed, on the level the first player asked for.
+const start = (players) => {
+    const seed  = Math.floor(Math.random() * 2 ** 32)
+    const level = players[0].level
+
+    players.forEach((player, i) => {
+        player.rematch = false
+        if (player.readyState === player.OPEN) player.send(`join ${i} ${seed} ${level}`)
+    })
+}
+
+const broadcast = (socket) => {
+    socket.on('message', data => {
+        const frame = data.toString()
+
+        // Spectators that join late start from the last keyframe.
+        if (frame.startsWith('key')) last_keyframe = frame
+
+        // Spectators on their way out are dropped instead of sent to.
+        watchers.forEach(watcher => {
+            if (watcher.readyState !== watcher.OPEN) return watchers.delete(watcher)
+            watcher.send(frame)
+        })
+    })
+}
+
+const watch = (socket) => {
+    watchers.add(socket)
+    socket.on('close', () => watchers.delete(socket))
+
+    if (last_keyframe) socket.send(last_keyframe)
+}
+
+relay.on('connection', (socket, request) => {
+    const url = new URL(request.url, 'http://localhost')
+
+    switch (url.pathname) {
+        case '/broadcast': return broadcast(socket)
+        case '/watch':     return watch(socket)
+        default:           return versus(socket, url.searchParams)
+    }
+})
+
+relay.on('listening', () => console.log(`Relay listening on port ${port}`))
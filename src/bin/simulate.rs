@@ -0,0 +1,288 @@
+//! Plays seeded games without a browser and reports how they went.
+//!
+//! ```text
+//! cargo run --release --bin simulate -- [options]
+//!
+//!   --level <n|file>      built-in level number or level file, the first built-in one by default
+//!   --generate <layout>   a generated level instead, like `?generate=` in the browser,
+//!                         made from the seed of every game
+//!   --width <n>           board size of generated levels, 16 by 10 like the browser
+//!   --height <n>
+//!   --edges <wrap|walls>  overrides the level
+//!   --speed <ms>          overrides how long a step takes at first
+//!   --speedup <factor>    overrides how much faster every apple makes the snake
+//!   --fastest <ms>        overrides the shortest a step can get
+//!
+//!   --bot <name>          autopilot (the default) or greedy
+//!   --replay <file>       plays a recorded game back instead of a bot
+//!   --record <file>       writes the game played as a replay, for a single game
+//!
+//!   --seed <n>            seed of the first game, 0 by default
+//!   --games <n>           how many games to play, one seed after the other
+//!   --max-ticks <n>       cuts games short after this many ticks
+//!   --trace <file|->      writes every tick as a line of JSON
+//! ```
+
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+
+use web_rust::bot::{Autopilot, Greedy, Strategy};
+use web_rust::game::{Context, Death, Events, new_game, step, step_duration};
+use web_rust::generator::{Layout, generate_level};
+use web_rust::level::{Level, builtin_levels, parse_level};
+use web_rust::replay::Replay;
+use web_rust::tournament::{Report, Run};
+
+/// The size of the board in the browser, for generated levels.
+const WIDTH: usize  = 16;
+const HEIGHT: usize = 10;
+
+struct Options
+{
+    /// The level a seed is played on. Always the same one, unless it's generated from the seed.
+    level: Box<dyn Fn(u64) -> Level>,
+    strategy: Box<dyn Strategy>,
+    replay: Option<Replay>,
+    record: Option<String>,
+
+    seed: u64,
+    games: usize,
+    max_ticks: usize,
+    trace: Option<String>
+}
+
+fn main()
+{
+    let options = match parse_options(std::env::args().skip(1).collect()) {
+        Ok(options) => options,
+        Err(error)  => {
+            eprintln!("{}", error);
+            eprintln!("See the top of src/bin/simulate.rs for the options.");
+            std::process::exit(2);
+        }
+    };
+
+    if let Err(error) = simulate(options) {
+        eprintln!("{}", error);
+        std::process::exit(1);
+    }
+}
+
+fn parse_options(args: Vec<String>) -> Result<Options, String>
+{
+    let mut level: Option<Level> = None;
+    let mut layout    = None;
+    let mut width     = WIDTH;
+    let mut height    = HEIGHT;
+    let mut wrap      = None;
+    let mut speed     = None;
+    let mut speed_up  = None;
+    let mut fastest   = None;
+    let mut strategy: Box<dyn Strategy> = Box::new(Autopilot);
+    let mut replay    = None;
+    let mut record    = None;
+    let mut seed      = 0;
+    let mut games     = 1;
+    let mut max_ticks = 100_000;
+    let mut trace     = None;
+
+    let mut args = args.into_iter();
+
+    while let Some(option) = args.next() {
+        let value = args.next().ok_or_else(|| format!("`{}` needs a value", option))?;
+
+        fn number<T: std::str::FromStr>(option: &str, value: &str) -> Result<T, String>
+        {
+            value.parse().map_err(|_| format!("`{}` must be a number, not `{}`", option, value))
+        }
+
+        match option.as_str() {
+            "--level"     => level = Some(load_level(&value)?),
+            "--generate"  => layout = Some(Layout::from_name(&value).ok_or_else(|| format!("Unknown layout `{}`", value))?),
+            "--width"     => width = number(&option, &value)?,
+            "--height"    => height = number(&option, &value)?,
+            "--edges"     => wrap = match value.as_str() {
+                "wrap"  => Some(true),
+                "walls" => Some(false),
+                _ => return Err(String::from("Edges must be either `wrap` or `walls`"))
+            },
+            "--speed"     => speed = Some(number(&option, &value)?),
+            "--speedup"   => speed_up = Some(number(&option, &value)?),
+            "--fastest"   => fastest = Some(number(&option, &value)?),
+            "--bot"       => strategy = match value.as_str() {
+                "autopilot" => Box::new(Autopilot),
+                "greedy"    => Box::new(Greedy),
+                _ => return Err(format!("Unknown bot `{}`", value))
+            },
+            "--replay"    => {
+                let text = std::fs::read_to_string(&value).map_err(|error| format!("{}: {}", value, error))?;
+                replay = Some(Replay::decode(&text).ok_or_else(|| format!("{} is not a replay", value))?);
+            }
+            "--record"    => record = Some(value),
+            "--seed"      => seed = number(&option, &value)?,
+            "--games"     => games = number(&option, &value)?,
+            "--max-ticks" => max_ticks = number(&option, &value)?,
+            "--trace"     => trace = Some(value),
+            _ => return Err(format!("Unknown option `{}`", option))
+        }
+    }
+
+    // A replay has its own seed, and only the one game to play.
+    if let Some(replay) = &replay {
+        seed  = replay.seed;
+        games = 1;
+    }
+
+    if level.is_some() && layout.is_some() {
+        return Err(String::from("Either `--level` or `--generate`, not both"))
+    }
+
+    let level = Box::new(move |seed| {
+        let mut level = match (&level, layout) {
+            (_, Some(layout)) => generate_level(layout, width, height, seed),
+            (Some(level), _)  => level.clone(),
+            (None, None)      => builtin_levels().remove(0)
+        };
+
+        if let Some(wrap) = wrap { level.wrap = wrap; }
+        if let Some(speed) = speed { level.speed = speed; }
+        if let Some(speed_up) = speed_up { level.speed_up = speed_up; }
+        if let Some(fastest) = fastest { level.fastest = fastest; }
+
+        level
+    });
+
+    if record.is_some() && games != 1 {
+        return Err(String::from("Only a single game can be recorded"))
+    }
+
+    Ok(Options { level, strategy, replay, record, seed, games, max_ticks, trace })
+}
+
+fn load_level(value: &str) -> Result<Level, String>
+{
+    if let Ok(index) = value.parse::<usize>() {
+        return builtin_levels().into_iter().nth(index).ok_or_else(|| format!("There's no built-in level {}", index))
+    }
+
+    let source = std::fs::read_to_string(value).map_err(|error| format!("{}: {}", value, error))?;
+    parse_level(&source).map_err(|error| format!("{}, {}", value, error))
+}
+
+fn simulate(mut options: Options) -> io::Result<()>
+{
+    // With the trace on standard output, everything else goes to standard error.
+    let mut trace: Option<Box<dyn Write>> = match options.trace.as_deref() {
+        None      => None,
+        Some("-") => Some(Box::new(io::stdout().lock())),
+        Some(path) => Some(Box::new(BufWriter::new(File::create(path)?)))
+    };
+
+    let mut out: Box<dyn Write> = match options.trace.as_deref() {
+        Some("-") => Box::new(io::stderr()),
+        _         => Box::new(io::stdout())
+    };
+
+    let strategy: &mut dyn Strategy = match options.replay.as_mut() {
+        Some(replay) => replay,
+        None         => options.strategy.as_mut()
+    };
+
+    let first = (options.level)(options.seed);
+    writeln!(out, "{}, {} by {}, played by {}", first.name, first.width, first.height, strategy.name())?;
+
+    let mut runs = vec![];
+
+    for seed in options.seed..options.seed + options.games as u64 {
+        let mut ctx       = new_game(&(options.level)(seed), 1, seed);
+        let mut recording = Replay::new(seed);
+
+        // How long the game took in the browser, at its speed curve.
+        let mut played = 0.;
+        let mut death  = None;
+
+        for _ in 0..options.max_ticks {
+            let input = strategy.next_move(&ctx, 0);
+            recording.record(&ctx, input);
+
+            played    += step_duration(&ctx);
+            let events = step(&mut ctx, &[Some(input)]);
+            death      = events.died.first().map(|&(_, death)| death);
+
+            if let Some(trace) = trace.as_mut() {
+                writeln!(trace, "{}", trace_line(&ctx, seed, input, &events, played))?;
+            }
+
+            let finished = ctx.target.is_some_and(|target| ctx.snakes[0].score >= target) || ctx.apple.is_none();
+            if death.is_some() || finished { break }
+        }
+
+        let snake = &ctx.snakes[0];
+        let end   = match death {
+            Some(death) => format!("died: {}", death_name(death)),
+            None        => String::from("survived")
+        };
+
+        writeln!
+        (
+            out,
+            "seed {:<6} score {:<5} length {:<5} ticks {:<7} time {}:{:04.1}   {}",
+            seed,
+            snake.score,
+            snake.body.len(),
+            ctx.ticks,
+            (played / 60_000.) as u64,
+            (played / 1000.) % 60.,
+            end
+        )?;
+
+        runs.push(Run { score: snake.score, ticks: ctx.ticks, death });
+
+        if let Some(path) = &options.record {
+            std::fs::write(path, recording.encode())?;
+        }
+    }
+
+    if runs.len() > 1 {
+        writeln!(out, "{}", Report::new(strategy.name(), &runs))?;
+    }
+
+    if let Some(trace) = trace.as_mut() { trace.flush()?; }
+
+    Ok(())
+}
+
+/// A tick as a line of JSON.
+fn trace_line(ctx: &Context, seed: u64, input: web_rust::Direction, events: &Events, played: f64) -> String
+{
+    let snake = &ctx.snakes[0];
+    let cell  = |(x, y): (usize, usize)| format!("[{},{}]", x, y);
+
+    format!
+    (
+        r#"{{"seed":{},"tick":{},"input":"{}","direction":"{}","head":{},"length":{},"score":{},"duration":{:.3},"time":{:.3},"apple":{},"ate":{},"died":{}}}"#,
+        seed,
+        ctx.ticks,
+        input.name(),
+        snake.direction.name(),
+        cell(snake.body[0]),
+        snake.body.len(),
+        snake.score,
+        ctx.tick_duration,
+        played,
+        ctx.apple.map_or(String::from("null"), |apple| cell(apple.position)),
+        events.ate.is_some(),
+        events.died.first().map_or(String::from("null"), |&(_, death)| format!("\"{}\"", death_name(death)))
+    )
+}
+
+fn death_name(death: Death) -> &'static str
+{
+    match death {
+        Death::Body     => "body",
+        Death::Obstacle => "obstacle",
+        Death::Wall     => "wall",
+        Death::Snake    => "snake",
+        Death::HeadOn   => "head-on"
+    }
+}
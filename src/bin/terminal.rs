@@ -0,0 +1,360 @@
+//! Plays snek in a terminal, on the same game core as the browser.
+//!
+//! ```text
+//! cargo run --release --bin terminal -- [level] [--versus] [--autopilot]
+//! ```
+//!
+//! The level is either the number of a built-in level or a level file.
+//! Every cell is drawn with half of a Unicode block, so two rows of the
+//! board fit in a line of text and cells come out square.
+
+use std::io::{self, Write};
+use std::time::{Duration, Instant, SystemTime};
+
+use crossterm::{cursor, execute, queue, terminal};
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use crossterm::style::{Color, Print, ResetColor, SetBackgroundColor, SetForegroundColor};
+
+use web_rust::{
+    APPLE_COLOUR, BACKGROUND_COLOUR, FLEEING_APPLE_COLOUR, OBSTACLE_COLOUR, PORTAL_COLOUR,
+    SECOND_SNAKE_COLOUR, SNAKE_COLOUR, Direction, terrain_colour
+};
+use web_rust::bot::autopilot;
+use web_rust::game::{AppleKind, Context, new_game, step, step_duration, survivor, terrain_at};
+use web_rust::level::{Level, builtin_levels, parse_level};
+
+/// Lines below the board, for the score and the controls.
+const STATUS_LINES: u16 = 2;
+
+/// Puts the terminal back the way it was however the game ends.
+struct RawMode;
+
+impl RawMode
+{
+    fn enter() -> io::Result<RawMode>
+    {
+        terminal::enable_raw_mode()?;
+        execute!(io::stdout(), terminal::EnterAlternateScreen, cursor::Hide)?;
+        Ok(RawMode)
+    }
+}
+
+impl Drop for RawMode
+{
+    fn drop(&mut self)
+    {
+        let _ = execute!(io::stdout(), ResetColor, cursor::Show, terminal::LeaveAlternateScreen);
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+struct Game
+{
+    level: Level,
+    players: usize,
+    ctx: Context,
+
+    /// The queued turn of every player.
+    keys: [Option<Direction>; 2],
+    autopilot: bool,
+    paused: bool,
+
+    /// How long the current game has been played for, pauses left out.
+    played: Duration,
+    /// Shown instead of the score, like the overlay in the browser.
+    message: Option<String>
+}
+
+impl Game
+{
+    fn new(level: Level, players: usize, autopilot: bool) -> Game
+    {
+        Game {
+            ctx: new_game(&level, players, seed()),
+            level,
+            players,
+            keys: [None; 2],
+            autopilot,
+            paused: true,
+            played: Duration::ZERO,
+            message: Some(String::from("Press space to start"))
+        }
+    }
+
+    fn restart(&mut self, message: String)
+    {
+        self.ctx     = new_game(&self.level, self.players, seed());
+        self.keys    = [None; 2];
+        self.paused  = true;
+        self.played  = Duration::ZERO;
+        self.message = Some(message);
+    }
+
+    /// Queues a turn the same way the browser does. Only a single turn is queued
+    /// at a time, and it can't be undone by the opposite key.
+    fn turn(&mut self, player: usize, direction: Direction)
+    {
+        let Some(snake) = self.ctx.snakes.get(player) else { return };
+        if snake.direction == direction.opposite() { return }
+
+        let queued = self.keys[player];
+        if queued.is_some_and(|queued| queued == direction || queued == direction.opposite()) { return }
+
+        self.keys[player] = Some(direction);
+    }
+
+    fn tick(&mut self)
+    {
+        let mut inputs = self.keys;
+        if self.autopilot { inputs[0] = autopilot(&self.ctx, 0); }
+
+        self.keys  = [None; 2];
+        let events = step(&mut self.ctx, &inputs[..self.players]);
+
+        if let Some(player) = events.ate {
+            let score = self.ctx.snakes[player].score;
+
+            if self.ctx.target.is_some_and(|target| score >= target) {
+                return match self.players {
+                    1 => self.restart(format!("Level complete with {} points!", score)),
+                    _ => self.restart(format!("Player {} wins!", player + 1))
+                }
+            }
+        }
+
+        if !events.died.is_empty() {
+            let message = match (self.players, survivor(&self.ctx, &events.died)) {
+                (1, _)            => format!("Game over! Your score was {}", self.ctx.snakes[0].score),
+                (_, Some(winner)) => format!("Player {} wins!", winner + 1),
+                (_, None)         => String::from("Draw!")
+            };
+
+            self.restart(message);
+        }
+    }
+
+    /// Handles a key press. Returns false once the player wants out.
+    fn key(&mut self, key: KeyEvent) -> bool
+    {
+        let arrows = self.players - 1;
+
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => return false,
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => return false,
+
+            KeyCode::Char(' ') => {
+                self.paused  = !self.paused;
+                self.message = self.paused.then(|| String::from("Paused"));
+            }
+            KeyCode::Char('p') => self.autopilot = !self.autopilot,
+
+            KeyCode::Char('w') => self.turn(0, Direction::Up),
+            KeyCode::Char('s') => self.turn(0, Direction::Down),
+            KeyCode::Char('a') => self.turn(0, Direction::Left),
+            KeyCode::Char('d') => self.turn(0, Direction::Right),
+
+            KeyCode::Up    => self.turn(arrows, Direction::Up),
+            KeyCode::Down  => self.turn(arrows, Direction::Down),
+            KeyCode::Left  => self.turn(arrows, Direction::Left),
+            KeyCode::Right => self.turn(arrows, Direction::Right),
+
+            _ => ()
+        }
+
+        true
+    }
+}
+
+fn main()
+{
+    let mut level     = builtin_levels().remove(0);
+    let mut players   = 1;
+    let mut autopilot = false;
+
+    for arg in std::env::args().skip(1) {
+        match arg.as_str() {
+            "--versus"    => players = 2,
+            "--autopilot" => autopilot = true,
+            arg => match load_level(arg) {
+                Ok(loaded) => level = loaded,
+                Err(error) => {
+                    eprintln!("{}", error);
+                    std::process::exit(1);
+                }
+            }
+        }
+    }
+
+    if let Err(error) = play(Game::new(level, players, autopilot)) {
+        eprintln!("{}", error);
+        std::process::exit(1);
+    }
+}
+
+fn load_level(arg: &str) -> Result<Level, String>
+{
+    if let Ok(index) = arg.parse::<usize>() {
+        return builtin_levels().into_iter().nth(index).ok_or_else(|| format!("There's no built-in level {}", index))
+    }
+
+    let source = std::fs::read_to_string(arg).map_err(|error| format!("{}: {}", arg, error))?;
+    parse_level(&source).map_err(|error| format!("{}, {}", arg, error))
+}
+
+fn play(mut game: Game) -> io::Result<()>
+{
+    let _raw_mode = RawMode::enter()?;
+    let mut out   = io::stdout();
+
+    let mut last_frame = Instant::now();
+    let mut next_tick  = Instant::now();
+
+    draw(&mut out, &game)?;
+
+    loop {
+        let timeout = match game.paused {
+            true  => Duration::from_millis(250),
+            false => next_tick.saturating_duration_since(Instant::now())
+        };
+
+        if event::poll(timeout)? {
+            match event::read()? {
+                Event::Key(key) if key.kind == KeyEventKind::Press => {
+                    if !game.key(key) { return Ok(()) }
+                }
+                Event::Resize(..) => queue!(out, terminal::Clear(terminal::ClearType::All))?,
+                _ => continue
+            }
+        }
+
+        let now = Instant::now();
+
+        match game.paused {
+            true => next_tick = now,
+            false => {
+                game.played += now - last_frame;
+
+                if now >= next_tick {
+                    next_tick = now + Duration::from_secs_f64(step_duration(&game.ctx) / 1000.);
+                    game.tick();
+                }
+            }
+        }
+
+        last_frame = now;
+        draw(&mut out, &game)?;
+    }
+}
+
+fn draw(out: &mut impl Write, game: &Game) -> io::Result<()>
+{
+    let ctx = &game.ctx;
+
+    // As big as fits, in whole characters per cell.
+    let (columns, lines) = terminal::size()?;
+    let scale = (columns as usize / ctx.width)
+        .min(2 * lines.saturating_sub(STATUS_LINES) as usize / ctx.height)
+        .max(1);
+
+    let width  = ctx.width * scale;
+    let height = ctx.height * scale;
+
+    // Row 0 of the board is at the bottom, so it's drawn last.
+    let pixel = |x: usize, y: usize| -> Option<Color> {
+        let row = height.checked_sub(y + 1)?;
+        Some(rgb(cell_colour(ctx, (x / scale, row / scale))))
+    };
+
+    for line in 0..height.div_ceil(2) {
+        queue!(out, cursor::MoveTo(0, line as u16))?;
+
+        for x in 0..width {
+            let top    = pixel(x, line * 2).unwrap();
+            let bottom = pixel(x, line * 2 + 1).unwrap_or(Color::Reset);
+
+            queue!(out, SetForegroundColor(top), SetBackgroundColor(bottom), Print('▀'))?;
+        }
+
+        queue!(out, ResetColor, terminal::Clear(terminal::ClearType::UntilNewLine))?;
+    }
+
+    let status = match (&game.message, game.players) {
+        (Some(message), _) => message.clone(),
+        (None, 1) => {
+            let snake = &ctx.snakes[0];
+            format!
+            (
+                "Score {}   Length {}   Speed {:.0} ms   {}:{:02}",
+                snake.score,
+                snake.body.len(),
+                step_duration(ctx),
+                game.played.as_secs() / 60,
+                game.played.as_secs() % 60
+            )
+        }
+        (None, _) => format!("Player 1: {}   Player 2: {}", ctx.snakes[0].score, ctx.snakes[1].score)
+    };
+
+    let controls = match game.players {
+        1 => "WASD or arrows to steer, space to pause, P for the bot, Q to quit",
+        _ => "WASD and arrows to steer, space to pause, P for the bot, Q to quit"
+    };
+
+    let line = height.div_ceil(2) as u16;
+
+    queue!
+    (
+        out,
+        cursor::MoveTo(0, line),
+        Print(status),
+        terminal::Clear(terminal::ClearType::UntilNewLine),
+        cursor::MoveTo(0, line + 1),
+        Print(if game.autopilot { "The bot is playing. P to take over, Q to quit" } else { controls }),
+        terminal::Clear(terminal::ClearType::UntilNewLine)
+    )?;
+
+    out.flush()
+}
+
+/// What's on a cell, drawn in the same colours as the browser.
+fn cell_colour(ctx: &Context, cell: (usize, usize)) -> [f32; 3]
+{
+    for (i, snake) in ctx.snakes.iter().enumerate() {
+        let Some(part) = snake.body.iter().position(|&part| part == cell) else { continue };
+
+        let colour = match i {
+            0 => SNAKE_COLOUR,
+            _ => SECOND_SNAKE_COLOUR
+        };
+
+        // The head is a shade lighter, so it's clear which way the snake is going.
+        return match part {
+            0 => colour.map(|channel| (channel * 1.4).min(1.)),
+            _ => colour
+        }
+    }
+
+    if let Some(apple) = ctx.apple.filter(|apple| apple.position == cell) {
+        return match apple.kind {
+            AppleKind::Still   => APPLE_COLOUR,
+            AppleKind::Fleeing => FLEEING_APPLE_COLOUR
+        }
+    }
+
+    if ctx.obstacles.contains(&cell) { return OBSTACLE_COLOUR }
+    if ctx.portals.iter().any(|&(one, two)| one == cell || two == cell) { return PORTAL_COLOUR }
+
+    terrain_at(ctx, cell).map_or(BACKGROUND_COLOUR, terrain_colour)
+}
+
+fn rgb([r, g, b]: [f32; 3]) -> Color
+{
+    Color::Rgb { r: (r * 255.) as u8, g: (g * 255.) as u8, b: (b * 255.) as u8 }
+}
+
+fn seed() -> u64
+{
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map_or(0, |since| since.as_nanos() as u64)
+}
@@ -0,0 +1,27 @@
+//! Plays every built-in level with every built-in strategy and prints how they did.
+//!
+//! ```text
+//! cargo run --release --bin tournament -- [games] [max ticks]
+//! ```
+
+use web_rust::bot::{Autopilot, Greedy, Strategy};
+use web_rust::level::builtin_levels;
+use web_rust::tournament::tournament;
+
+fn main()
+{
+    let mut args = std::env::args().skip(1);
+
+    let games     = args.next().and_then(|games| games.parse().ok()).unwrap_or(20);
+    let max_ticks = args.next().and_then(|ticks| ticks.parse().ok()).unwrap_or(10_000);
+
+    let mut strategies: Vec<Box<dyn Strategy>> = vec![Box::new(Autopilot), Box::new(Greedy)];
+
+    for level in builtin_levels() {
+        println!("{}", level.name);
+
+        for report in tournament(&mut strategies, &level, games, max_ticks) {
+            println!("  {}", report);
+        }
+    }
+}
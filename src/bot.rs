@@ -0,0 +1,327 @@
+use std::collections::VecDeque;
+
+use crate::Direction;
+use crate::game::{Cell, Context, next_cell, terrain_at};
+use crate::level::Terrain;
+
+const DIRECTIONS: [Direction; 4] = [Direction::Up, Direction::Down, Direction::Left, Direction::Right];
+
+/// Once the snake takes up this much of the free board,
+/// it stops chasing apples and follows a Hamiltonian cycle if it can.
+const CROWDED: f64 = 0.5;
+
+/// A way of playing the game. Anything that implements this
+/// can take part in a tournament.
+pub trait Strategy
+{
+    /// What the strategy is called in reports.
+    fn name(&self) -> String;
+
+    /// Looks at the game and picks where the snake of `player` goes next.
+    /// Going the way it already is means not turning.
+    fn next_move(&mut self, ctx: &Context, player: usize) -> Direction;
+}
+
+/// The built-in bot, the same one that takes over in the browser.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Autopilot;
+
+impl Strategy for Autopilot
+{
+    fn name(&self) -> String
+    {
+        String::from("autopilot")
+    }
+
+    fn next_move(&mut self, ctx: &Context, player: usize) -> Direction
+    {
+        autopilot(ctx, player).unwrap_or(ctx.snakes[player].direction)
+    }
+}
+
+/// Always takes the shortest way to the apple, without checking where it
+/// leaves the snake. Good for seeing what the autopilot's caution is worth.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Greedy;
+
+impl Strategy for Greedy
+{
+    fn name(&self) -> String
+    {
+        String::from("greedy")
+    }
+
+    fn next_move(&mut self, ctx: &Context, player: usize) -> Direction
+    {
+        let snake   = &ctx.snakes[player];
+        let blocked = blocked_cells(ctx);
+
+        ctx.apple
+            .and_then(|apple| shortest_path(ctx, &blocked, (snake.body[0], snake.direction), apple.position))
+            .map(|path| path[0].0)
+            .or_else(|| stall(ctx, player, &blocked))
+            .unwrap_or(snake.direction)
+    }
+}
+
+/// Picks the input for a snake, the way a careful player would.
+///
+/// The shortest way to the apple is only taken if the snake can still
+/// reach its own tail once it gets there, so it never eats its way into
+/// a dead end. On a crowded board it follows a cycle through every cell
+/// instead, which is slow but can't go wrong. Failing both, it goes
+/// wherever leaves it the most room.
+///
+/// Nothing is returned with every way out blocked.
+pub fn autopilot(ctx: &Context, player: usize) -> Option<Direction>
+{
+    let snake = ctx.snakes.get(player)?;
+    let head  = snake.body[0];
+
+    let blocked = blocked_cells(ctx);
+
+    let free    = ctx.width * ctx.height - ctx.obstacles.len();
+    let crowded = snake.body.len() as f64 >= free as f64 * CROWDED;
+
+    if crowded {
+        let along_cycle = hamiltonian_cycle(ctx)
+            .and_then(|cycle| cycle[head.1 * ctx.width + head.0])
+            .filter(|&direction| moves(ctx, head, snake.direction).contains(&direction))
+            .and_then(|direction| move_to(ctx, &blocked, head, direction).map(|cell| (direction, cell)))
+            .filter(|&step| tail_reachable_after(ctx, player, &[step], 0));
+
+        if let Some((direction, _)) = along_cycle { return Some(direction) }
+    }
+
+    if let Some(apple) = ctx.apple {
+        if let Some(path) = shortest_path(ctx, &blocked, (head, snake.direction), apple.position) {
+            if tail_reachable_after(ctx, player, &path, 1) {
+                return Some(path[0].0)
+            }
+        }
+    }
+
+    stall(ctx, player, &blocked)
+}
+
+/// Picks the move that keeps the snake alive the longest, for when there's
+/// nothing safe to eat. Being able to reach the tail matters most,
+/// as the way out opens up behind it.
+fn stall(ctx: &Context, player: usize, blocked: &[bool]) -> Option<Direction>
+{
+    let snake = &ctx.snakes[player];
+    let head  = snake.body[0];
+
+    moves(ctx, head, snake.direction)
+        .into_iter()
+        .filter_map(|direction| move_to(ctx, blocked, head, direction).map(|cell| (direction, cell)))
+        .max_by_key(|&(direction, cell)| {
+            let reaches_tail = tail_reachable_after(ctx, player, &[(direction, cell)], 0);
+            (reaches_tail, room(ctx, blocked, (cell, direction)))
+        })
+        .map(|(direction, _)| direction)
+}
+
+/// Cells that can't be moved into on the next step: obstacles and snakes,
+/// apart from tails that are about to move out of the way.
+pub(crate) fn blocked_cells(ctx: &Context) -> Vec<bool>
+{
+    let mut blocked = vec![false; ctx.width * ctx.height];
+
+    for &(x, y) in &ctx.obstacles {
+        blocked[y * ctx.width + x] = true;
+    }
+
+    for snake in &ctx.snakes {
+        let moving_tail = if snake.growth == 0 { 1 } else { 0 };
+
+        for &(x, y) in &snake.body[..snake.body.len() - moving_tail] {
+            blocked[y * ctx.width + x] = true;
+        }
+    }
+
+    blocked
+}
+
+/// The cell reached by moving from `cell`, if it's free and can be entered that way.
+pub(crate) fn move_to(ctx: &Context, blocked: &[bool], cell: Cell, direction: Direction) -> Option<Cell>
+{
+    let next = next_cell(ctx, cell, direction)?;
+    if blocked[next.1 * ctx.width + next.0] { return None }
+
+    match terrain_at(ctx, next) {
+        Some(Terrain::Gate(gate)) if gate != direction => None,
+        Some(Terrain::Conveyor(belt)) if belt == direction.opposite() => None,
+        _ => Some(next)
+    }
+}
+
+/// The directions a snake facing `facing` can actually move in from `cell`,
+/// the same way the game steers it. Ice and conveyors leave only the one.
+fn moves(ctx: &Context, cell: Cell, facing: Direction) -> Vec<Direction>
+{
+    match terrain_at(ctx, cell) {
+        Some(Terrain::Ice) => vec![facing],
+        Some(Terrain::Conveyor(direction)) if direction != facing.opposite() => vec![direction],
+        Some(Terrain::Conveyor(_)) => vec![facing],
+        _ => DIRECTIONS.into_iter().filter(|direction| *direction != facing.opposite()).collect()
+    }
+}
+
+/// Index of a cell and the direction it was entered in, for searches that need both.
+fn state_index(ctx: &Context, (cell, facing): (Cell, Direction)) -> usize
+{
+    let direction = DIRECTIONS.iter().position(|direction| *direction == facing).unwrap();
+    (cell.1 * ctx.width + cell.0) * DIRECTIONS.len() + direction
+}
+
+/// Breadth first search over the free cells, through portals and the board edges.
+/// Returns every move on the way along with the cell it ends up on.
+///
+/// Where the snake can go depends on which way it's facing, so that's part of the search.
+fn shortest_path(ctx: &Context, blocked: &[bool], from: (Cell, Direction), to: Cell) -> Option<Vec<(Direction, Cell)>>
+{
+    let mut came_from: Vec<Option<(Cell, Direction)>> = vec![None; ctx.width * ctx.height * DIRECTIONS.len()];
+    let mut queue = VecDeque::from([from]);
+
+    came_from[state_index(ctx, from)] = Some(from);
+
+    while let Some(state) = queue.pop_front() {
+        if state.0 == to && state != from {
+            let mut path  = vec![];
+            let mut state = state;
+
+            while state != from {
+                path.push((state.1, state.0));
+                state = came_from[state_index(ctx, state)]?;
+            }
+
+            path.reverse();
+            return Some(path)
+        }
+
+        for direction in moves(ctx, state.0, state.1) {
+            let Some(next) = move_to(ctx, blocked, state.0, direction) else { continue };
+
+            let index = state_index(ctx, (next, direction));
+            if came_from[index].is_some() { continue }
+
+            came_from[index] = Some(state);
+            queue.push_back((next, direction));
+        }
+    }
+
+    None
+}
+
+/// Moves a copy of the snake along the path and checks that its head
+/// can still get to its tail from there. `grows` is how many cells
+/// longer it gets on the way, on top of any growth already due.
+fn tail_reachable_after(ctx: &Context, player: usize, path: &[(Direction, Cell)], grows: usize) -> bool
+{
+    let snake = &ctx.snakes[player];
+
+    let mut body: VecDeque<Cell> = snake.body.iter().copied().collect();
+    let mut growth = snake.growth;
+
+    for (i, &(_, cell)) in path.iter().enumerate() {
+        body.push_front(cell);
+
+        // The apple only makes the snake longer after it's eaten.
+        if i + 1 == path.len() { growth += grows; }
+
+        match growth {
+            0 => { body.pop_back(); }
+            _ => growth -= 1
+        }
+    }
+
+    let mut blocked = vec![false; ctx.width * ctx.height];
+
+    for &(x, y) in &ctx.obstacles { blocked[y * ctx.width + x] = true; }
+    for (i, other) in ctx.snakes.iter().enumerate() {
+        if i == player { continue }
+        for &(x, y) in &other.body { blocked[y * ctx.width + x] = true; }
+    }
+    for &(x, y) in body.iter().skip(1) { blocked[y * ctx.width + x] = true; }
+
+    let (head, tail) = (body[0], body[body.len() - 1]);
+    let facing = path.last().map_or(snake.direction, |&(direction, _)| direction);
+
+    // The tail moves out of the way as the snake follows it.
+    blocked[tail.1 * ctx.width + tail.0] = false;
+
+    shortest_path(ctx, &blocked, (head, facing), tail).is_some()
+}
+
+/// How many free cells can be reached from where the snake is headed.
+fn room(ctx: &Context, blocked: &[bool], from: (Cell, Direction)) -> usize
+{
+    let mut seen  = vec![false; ctx.width * ctx.height * DIRECTIONS.len()];
+    let mut cells = vec![false; ctx.width * ctx.height];
+    let mut queue = VecDeque::from([from]);
+    seen[state_index(ctx, from)] = true;
+
+    while let Some((cell, facing)) = queue.pop_front() {
+        cells[cell.1 * ctx.width + cell.0] = true;
+
+        for direction in moves(ctx, cell, facing) {
+            let Some(next) = move_to(ctx, blocked, cell, direction) else { continue };
+
+            let index = state_index(ctx, (next, direction));
+            if seen[index] { continue }
+
+            seen[index] = true;
+            queue.push_back((next, direction));
+        }
+    }
+
+    cells.iter().filter(|&&seen| seen).count()
+}
+
+/// A cycle through every cell of the board, as the direction to move in
+/// from each cell. Only open boards with an even side have one that's easy
+/// to find, so anything with walls, portals or terrain in the way gets none.
+///
+/// The cycle runs up the first column, then snakes through the rest of the
+/// board row by row before coming back down to where it started.
+fn hamiltonian_cycle(ctx: &Context) -> Option<Vec<Option<Direction>>>
+{
+    let open = ctx.obstacles.is_empty()
+        && ctx.portals.is_empty()
+        && ctx.terrain.iter().all(|terrain| matches!(terrain, None | Some(Terrain::Mud)));
+
+    if !open || ctx.width < 2 || ctx.height < 2 { return None }
+
+    // Works along rows if there's an even number of them, along columns otherwise.
+    let (rows, columns, transposed) = match (ctx.height % 2, ctx.width % 2) {
+        (0, _) => (ctx.height, ctx.width, false),
+        (_, 0) => (ctx.width, ctx.height, true),
+        _ => return None
+    };
+
+    let mut order: Vec<(usize, usize)> = vec![];
+
+    for row in 0..rows {
+        match row % 2 {
+            0 => order.extend((1..columns).map(|column| (column, row))),
+            _ => order.extend((1..columns).rev().map(|column| (column, row)))
+        }
+    }
+    order.extend((0..rows).rev().map(|row| (0, row)));
+
+    let mut cycle = vec![None; ctx.width * ctx.height];
+
+    for (i, &(column, row)) in order.iter().enumerate() {
+        let (next_column, next_row) = order[(i + 1) % order.len()];
+
+        let (x, y, dx, dy) = match transposed {
+            false => (column, row, next_column as isize - column as isize, next_row as isize - row as isize),
+            true  => (row, column, next_row as isize - row as isize, next_column as isize - column as isize)
+        };
+
+        cycle[y * ctx.width + x] = DIRECTIONS.into_iter().find(|direction| direction.offset() == (dx, dy));
+    }
+
+    Some(cycle)
+}
@@ -0,0 +1,74 @@
+use crate::level::{Level, parse_level};
+
+const PROGRESS_KEY: &str = "snek-campaign-progress";
+
+/// A sequence of levels, each completed by reaching its target.
+#[derive(Debug)]
+pub struct Campaign
+{
+    pub levels: Vec<Level>,
+    pub current: usize
+}
+
+impl Campaign
+{
+    /// Picks the campaign up after the first `completed` levels.
+    /// Nothing if that's all of them, or there are none.
+    pub fn new(levels: Vec<Level>, completed: usize) -> Option<Campaign>
+    {
+        if completed >= levels.len() { return None }
+        Some(Campaign { levels, current: completed })
+    }
+
+    pub fn level(&self) -> &Level
+    {
+        &self.levels[self.current]
+    }
+
+    /// Moves on to the next level. Returns false if the campaign is over.
+    pub fn advance(&mut self) -> bool
+    {
+        if self.current + 1 >= self.levels.len() { return false }
+        self.current += 1;
+        true
+    }
+}
+
+pub fn builtin_campaign() -> Vec<Level>
+{
+    [
+        include_str!("../levels/campaign/01_warm_up.txt"),
+        include_str!("../levels/campaign/02_pillars.txt"),
+        include_str!("../levels/campaign/03_boxed_in.txt"),
+        include_str!("../levels/campaign/04_corridors.txt"),
+        include_str!("../levels/campaign/05_fortress.txt"),
+        include_str!("../levels/campaign/06_rush_hour.txt"),
+    ]
+        .into_iter()
+        .map(|source| parse_level(source).expect("Campaign level failed to parse"))
+        .collect()
+}
+
+/// Returns how many campaign levels have been completed, as stored in `localStorage`.
+pub fn load_progress() -> usize
+{
+    local_storage()
+        .and_then(|storage| storage.get_item(PROGRESS_KEY).ok().flatten())
+        .and_then(|progress| progress.parse().ok())
+        .unwrap_or(0)
+}
+
+/// Stores the campaign progress. Progress never goes backwards.
+pub fn save_progress(completed: usize)
+{
+    if completed <= load_progress() { return }
+
+    if let Some(storage) = local_storage() {
+        let _ = storage.set_item(PROGRESS_KEY, &completed.to_string());
+    }
+}
+
+fn local_storage() -> Option<web_sys::Storage>
+{
+    web_sys::window()?.local_storage().ok().flatten()
+}
@@ -0,0 +1,116 @@
+use wasm_bindgen::prelude::*;
+use web_sys::{CanvasRenderingContext2d, HtmlCanvasElement};
+
+use crate::{BACKGROUND_COLOUR, SCENE_HEIGHT, SCENE_WIDTH};
+use crate::render::{Renderer, letterbox};
+
+/// Draws the board with the 2D canvas API, for browsers without WebGL 2.
+///
+/// The canvas can't blend colours across a triangle, so every triangle is
+/// filled with the average of its vertex colours. Everything on the board
+/// is a single colour anyway.
+pub struct Canvas2d
+{
+    context: CanvasRenderingContext2d,
+    width: f64,
+    height: f64
+}
+
+impl Canvas2d
+{
+    pub fn new(canvas: &HtmlCanvasElement) -> Result<Canvas2d, JsValue>
+    {
+        let context = canvas
+            .get_context("2d")?
+            .ok_or_else(|| JsValue::from_str("The canvas has no 2D context"))?
+            .dyn_into::<CanvasRenderingContext2d>()?;
+
+        Ok(Canvas2d { context, width: canvas.width() as f64, height: canvas.height() as f64 })
+    }
+}
+
+impl Renderer for Canvas2d
+{
+    fn backend(&self) -> &'static str
+    {
+        "canvas2d"
+    }
+
+    fn resize(&mut self, width: u32, height: u32)
+    {
+        self.width  = width as f64;
+        self.height = height as f64;
+    }
+
+    fn render(&mut self, positions: &[f32], colours: &[f32]) -> Result<(), String>
+    {
+        if positions.len() / 2 != colours.len() / 3 {
+            return Err(format!("{} vertices but {} colours", positions.len() / 2, colours.len() / 3))
+        }
+
+        let context = &self.context;
+        let (x, y, width, height) = letterbox(self.width, self.height);
+
+        // Outside of the scene the page shows through.
+        context.set_transform(1., 0., 0., 1., 0., 0.).map_err(|_| "Failed to reset the transform")?;
+        context.clear_rect(0., 0., self.width, self.height);
+
+        // The scene counts from the bottom left corner, the canvas from the top left one.
+        context
+            .set_transform
+            (
+                width / SCENE_WIDTH as f64,
+                0.,
+                0.,
+                -height / SCENE_HEIGHT as f64,
+                x,
+                self.height - y
+            )
+            .map_err(|_| "Failed to scale the scene")?;
+
+        context.set_fill_style_str(&css_colour(BACKGROUND_COLOUR));
+        context.fill_rect(0., 0., SCENE_WIDTH as f64, SCENE_HEIGHT as f64);
+
+        let triangles = positions.chunks_exact(6).zip(colours.chunks_exact(9));
+
+        // Triangles of the same colour in a row go in a single path, so the
+        // two halves of a box don't leave an antialiased seam between them.
+        let mut current: Option<String> = None;
+
+        for (vertices, colours) in triangles {
+            let colour = css_colour(std::array::from_fn(|c| (colours[c] + colours[c + 3] + colours[c + 6]) / 3.));
+
+            if current.as_ref() != Some(&colour) {
+                if current.is_some() { context.fill(); }
+
+                context.begin_path();
+                context.set_fill_style_str(&colour);
+                current = Some(colour);
+            }
+
+            let [a, b, c] = [0, 2, 4].map(|i| (vertices[i] as f64, vertices[i + 1] as f64));
+
+            // All wound the same way, or overlapping triangles would cut holes in each other.
+            let clockwise = (b.0 - a.0) * (c.1 - a.1) < (b.1 - a.1) * (c.0 - a.0);
+            let (b, c)    = match clockwise {
+                true  => (c, b),
+                false => (b, c)
+            };
+
+            context.move_to(a.0, a.1);
+            context.line_to(b.0, b.1);
+            context.line_to(c.0, c.1);
+            context.close_path();
+        }
+
+        if current.is_some() { context.fill(); }
+
+        Ok(())
+    }
+}
+
+fn css_colour(colour: [f32; 3]) -> String
+{
+    let [r, g, b] = colour.map(|channel| (channel.clamp(0., 1.) * 255.).round() as u8);
+    format!("rgb({}, {}, {})", r, g, b)
+}
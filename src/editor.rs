@@ -0,0 +1,200 @@
+use crate::Direction;
+use crate::level::{Level, Terrain, write_level};
+
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub enum Tile {
+    Empty,
+    Wall,
+    Portal,
+    Start,
+    Apple,
+    Terrain(Terrain)
+}
+
+impl Tile
+{
+    fn symbol(self) -> char
+    {
+        match self {
+            Tile::Empty            => '.',
+            Tile::Wall             => '#',
+            Tile::Portal           => 'P',
+            Tile::Start            => 'S',
+            Tile::Apple            => 'A',
+            Tile::Terrain(terrain) => terrain.symbol()
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+struct Snapshot
+{
+    tiles: Vec<Tile>,
+    direction: Direction
+}
+
+/// State of the level editor. Tiles are stored row by row,
+/// starting from the bottom left corner like the rest of the grid.
+#[derive(Debug)]
+pub struct Editor
+{
+    pub width: usize,
+    pub height: usize,
+    tiles: Vec<Tile>,
+
+    pub direction: Direction,
+    pub name: String,
+    pub wrap: bool,
+    pub speed: f64,
+    speed_up: f64,
+    fastest: f64,
+    pub target: Option<usize>,
+    apple_lifetime: Option<usize>,
+    fleeing: f64,
+
+    pub brush: Tile,
+    pub hover: Option<(usize, usize)>,
+    pub painting: bool,
+    pub playtesting: bool,
+
+    undo_stack: Vec<Snapshot>,
+    redo_stack: Vec<Snapshot>
+}
+
+impl Editor
+{
+    pub fn from_level(level: &Level) -> Editor
+    {
+        let mut tiles = vec![Tile::Empty; level.width * level.height];
+
+        for &(x, y) in &level.obstacles { tiles[y * level.width + x] = Tile::Wall; }
+        for &(x, y) in &level.apples { tiles[y * level.width + x] = Tile::Apple; }
+        for &((x, y), terrain) in &level.terrain { tiles[y * level.width + x] = Tile::Terrain(terrain); }
+        for &(one, two) in &level.portals {
+            tiles[one.1 * level.width + one.0] = Tile::Portal;
+            tiles[two.1 * level.width + two.0] = Tile::Portal;
+        }
+        tiles[level.start.1 * level.width + level.start.0] = Tile::Start;
+
+        Editor {
+            width: level.width,
+            height: level.height,
+            tiles,
+            direction: level.direction,
+            name: level.name.clone(),
+            wrap: level.wrap,
+            speed: level.speed,
+            speed_up: level.speed_up,
+            fastest: level.fastest,
+            target: level.target,
+            apple_lifetime: level.apple_lifetime,
+            fleeing: level.fleeing,
+            brush: Tile::Wall,
+            hover: None,
+            painting: false,
+            playtesting: false,
+            undo_stack: vec![],
+            redo_stack: vec![]
+        }
+    }
+
+    pub fn tile(&self, x: usize, y: usize) -> Tile
+    {
+        self.tiles[y * self.width + x]
+    }
+
+    /// Every mouse press is a single undo step, no matter how many cells it paints.
+    pub fn begin_stroke(&mut self)
+    {
+        self.undo_stack.push(self.snapshot());
+        self.redo_stack.clear();
+        self.painting = true;
+    }
+
+    pub fn end_stroke(&mut self)
+    {
+        self.painting = false;
+    }
+
+    /// Paints the current brush onto a cell. Painting the start over
+    /// itself rotates the starting direction clockwise.
+    pub fn paint(&mut self, x: usize, y: usize, rotate: bool)
+    {
+        if x >= self.width || y >= self.height { return }
+
+        let index = y * self.width + x;
+
+        if self.brush == Tile::Start {
+            if self.tiles[index] == Tile::Start {
+                if rotate {
+                    self.direction = match self.direction {
+                        Direction::Up    => Direction::Right,
+                        Direction::Right => Direction::Down,
+                        Direction::Down  => Direction::Left,
+                        Direction::Left  => Direction::Up
+                    };
+                }
+                return
+            }
+
+            // There is only ever a single start.
+            for tile in self.tiles.iter_mut().filter(|tile| **tile == Tile::Start) {
+                *tile = Tile::Empty;
+            }
+        }
+
+        self.tiles[index] = self.brush;
+    }
+
+    pub fn undo(&mut self)
+    {
+        let Some(snapshot) = self.undo_stack.pop() else { return };
+        self.redo_stack.push(self.snapshot());
+        self.restore(snapshot);
+    }
+
+    pub fn redo(&mut self)
+    {
+        let Some(snapshot) = self.redo_stack.pop() else { return };
+        self.undo_stack.push(self.snapshot());
+        self.restore(snapshot);
+    }
+
+    /// Writes the level out in the text format read by `parse_level`.
+    pub fn export(&self) -> String
+    {
+        // Only the rules come from the level, the tiles go out as they are.
+        let rules = Level {
+            name: self.name.clone(),
+            width: self.width,
+            height: self.height,
+            wrap: self.wrap,
+            speed: self.speed,
+            speed_up: self.speed_up,
+            fastest: self.fastest,
+            target: self.target,
+            apple_lifetime: self.apple_lifetime,
+            fleeing: self.fleeing,
+            start: (0, 0),
+            direction: self.direction,
+            obstacles: vec![],
+            apples: vec![],
+            portals: vec![],
+            terrain: vec![]
+        };
+        let grid: Vec<char> = self.tiles.iter().map(|tile| tile.symbol()).collect();
+
+        write_level(&rules, &grid)
+    }
+
+    fn snapshot(&self) -> Snapshot
+    {
+        Snapshot { tiles: self.tiles.clone(), direction: self.direction }
+    }
+
+    fn restore(&mut self, snapshot: Snapshot)
+    {
+        self.tiles     = snapshot.tiles;
+        self.direction = snapshot.direction;
+    }
+}
@@ -0,0 +1,279 @@
+use web_sys::{
+    WebGl2RenderingContext as Gl, WebGlBuffer, WebGlFramebuffer, WebGlProgram, WebGlTexture, WebGlVertexArrayObject
+};
+
+use crate::{APPLE_COLOUR, FLEEING_APPLE_COLOUR};
+use crate::render::Effect;
+use crate::webgl::{compile_shader, link_program};
+
+/// Draws effects over a finished frame.
+///
+/// The scene is drawn into a texture instead of the screen, and every effect
+/// is a fragment shader run over the whole of it, reading what the one before
+/// drew. The last one draws to the screen. Two textures are enough for any
+/// number of effects, each pass reads one and draws into the other.
+pub struct PostProcess
+{
+    programs: Vec<(Effect, WebGlProgram)>,
+    targets: [Target; 2],
+    /// A triangle covering the whole screen, and then some.
+    triangle: WebGlVertexArrayObject,
+    corners: WebGlBuffer,
+    width: i32,
+    height: i32
+}
+
+struct Target
+{
+    framebuffer: WebGlFramebuffer,
+    texture: WebGlTexture
+}
+
+impl PostProcess
+{
+    pub fn new(context: &Gl) -> Result<PostProcess, String>
+    {
+        let programs = Effect::ALL
+            .iter()
+            .map(|&effect| Ok((effect, effect_program(context, effect)?)))
+            .collect::<Result<Vec<_>, String>>()?;
+
+        let triangle = context.create_vertex_array().ok_or("Failed to create vertex array object")?;
+        context.bind_vertex_array(Some(&triangle));
+
+        let buffer = context.create_buffer().ok_or("Failed to create buffer")?;
+        context.bind_buffer(Gl::ARRAY_BUFFER, Some(&buffer));
+
+        let corners = js_sys::Float32Array::from(&[-1., -1., 3., -1., -1., 3.][..]);
+        context.buffer_data_with_array_buffer_view(Gl::ARRAY_BUFFER, &corners, Gl::STATIC_DRAW);
+
+        // The vertex shader puts the corners at location 0, so the one array works with every program.
+        context.vertex_attrib_pointer_with_i32(0, 2, Gl::FLOAT, false, 0, 0);
+        context.enable_vertex_attrib_array(0);
+
+        let targets = [create_target(context)?, create_target(context)?];
+
+        Ok(PostProcess { programs, targets, triangle, corners: buffer, width: 0, height: 0 })
+    }
+
+    /// Lets go of everything it made, for when it's replaced or not needed anymore.
+    pub fn delete(&self, context: &Gl)
+    {
+        for (_, program) in &self.programs {
+            context.delete_program(Some(program));
+        }
+
+        for target in &self.targets {
+            context.delete_framebuffer(Some(&target.framebuffer));
+            context.delete_texture(Some(&target.texture));
+        }
+
+        context.delete_vertex_array(Some(&self.triangle));
+        context.delete_buffer(Some(&self.corners));
+    }
+
+    /// Makes the textures as big as the part of the screen the scene is drawn on.
+    pub fn resize(&mut self, context: &Gl, width: i32, height: i32) -> Result<(), String>
+    {
+        if (width, height) == (self.width, self.height) { return Ok(()) }
+
+        for target in &self.targets {
+            context.bind_texture(Gl::TEXTURE_2D, Some(&target.texture));
+            context
+                .tex_image_2d_with_i32_and_i32_and_i32_and_format_and_type_and_opt_u8_array
+                (
+                    Gl::TEXTURE_2D, 0, Gl::RGBA as i32, width, height, 0, Gl::RGBA, Gl::UNSIGNED_BYTE, None
+                )
+                .map_err(|_| "Failed to size the effect textures")?;
+        }
+
+        self.width  = width;
+        self.height = height;
+
+        Ok(())
+    }
+
+    /// Sends whatever's drawn next into the first texture instead of the screen.
+    pub fn begin(&self, context: &Gl)
+    {
+        context.bind_framebuffer(Gl::FRAMEBUFFER, Some(&self.targets[0].framebuffer));
+        context.viewport(0, 0, self.width, self.height);
+        context.disable(Gl::SCISSOR_TEST);
+    }
+
+    /// Runs the effects one after the other, the last one onto the part
+    /// of the screen at `viewport`. `time` is in seconds.
+    pub fn finish(&self, context: &Gl, effects: &[Effect], viewport: [i32; 4], time: f32)
+    {
+        context.bind_vertex_array(Some(&self.triangle));
+        context.active_texture(Gl::TEXTURE0);
+
+        for (i, effect) in effects.iter().enumerate() {
+            let Some((_, program)) = self.programs.iter().find(|(e, _)| e == effect) else { continue };
+
+            match i == effects.len() - 1 {
+                true => {
+                    let [x, y, width, height] = viewport;
+
+                    context.bind_framebuffer(Gl::FRAMEBUFFER, None);
+                    context.viewport(x, y, width, height);
+                    context.enable(Gl::SCISSOR_TEST);
+                }
+                false => context.bind_framebuffer(Gl::FRAMEBUFFER, Some(&self.targets[(i + 1) % 2].framebuffer))
+            }
+
+            context.use_program(Some(program));
+            context.bind_texture(Gl::TEXTURE_2D, Some(&self.targets[i % 2].texture));
+
+            context.uniform1i(context.get_uniform_location(program, "scene").as_ref(), 0);
+            context.uniform1f(context.get_uniform_location(program, "time").as_ref(), time);
+            context.uniform2f
+            (
+                context.get_uniform_location(program, "resolution").as_ref(),
+                self.width as f32,
+                self.height as f32
+            );
+
+            context.draw_arrays(Gl::TRIANGLES, 0, 3);
+        }
+    }
+}
+
+fn create_target(context: &Gl) -> Result<Target, String>
+{
+    let texture = context.create_texture().ok_or("Failed to create texture")?;
+    context.bind_texture(Gl::TEXTURE_2D, Some(&texture));
+
+    // Blended between pixels, and never read past the edges.
+    context.tex_parameteri(Gl::TEXTURE_2D, Gl::TEXTURE_MIN_FILTER, Gl::LINEAR as i32);
+    context.tex_parameteri(Gl::TEXTURE_2D, Gl::TEXTURE_MAG_FILTER, Gl::LINEAR as i32);
+    context.tex_parameteri(Gl::TEXTURE_2D, Gl::TEXTURE_WRAP_S, Gl::CLAMP_TO_EDGE as i32);
+    context.tex_parameteri(Gl::TEXTURE_2D, Gl::TEXTURE_WRAP_T, Gl::CLAMP_TO_EDGE as i32);
+
+    let framebuffer = context.create_framebuffer().ok_or("Failed to create framebuffer")?;
+    context.bind_framebuffer(Gl::FRAMEBUFFER, Some(&framebuffer));
+    context.framebuffer_texture_2d(Gl::FRAMEBUFFER, Gl::COLOR_ATTACHMENT0, Gl::TEXTURE_2D, Some(&texture), 0);
+    context.bind_framebuffer(Gl::FRAMEBUFFER, None);
+
+    Ok(Target { framebuffer, texture })
+}
+
+fn effect_program(context: &Gl, effect: Effect) -> Result<WebGlProgram, String>
+{
+    let source = format!("{}{}", FRAGMENT_HEADER, fragment_shader(effect));
+
+    let program = compile_shader(context, Gl::VERTEX_SHADER, VERTEX_SHADER)
+        .and_then(|vertex_shader| Ok((vertex_shader, compile_shader(context, Gl::FRAGMENT_SHADER, &source)?)))
+        .and_then(|(vertex_shader, fragment_shader)| link_program(context, &vertex_shader, &fragment_shader))
+        .map_err(|error| format!("{}: {}", effect.name(), error))?;
+
+    // Bloom lights up the apple, whichever kind it is.
+    context.use_program(Some(&program));
+    context.uniform3fv_with_f32_array(context.get_uniform_location(&program, "apple").as_ref(), &APPLE_COLOUR);
+    context.uniform3fv_with_f32_array(context.get_uniform_location(&program, "fleeingApple").as_ref(), &FLEEING_APPLE_COLOUR);
+
+    Ok(program)
+}
+
+const VERTEX_SHADER: &str = r##"#version 300 es
+
+layout(location = 0) in vec2 corner;
+out vec2 uv;
+
+void main() {
+    uv = corner * 0.5 + 0.5;
+    gl_Position = vec4(corner, 0, 1);
+}
+"##;
+
+/// What every effect gets: the frame so far, its size in pixels and the time in seconds.
+const FRAGMENT_HEADER: &str = r##"#version 300 es
+
+precision highp float;
+
+uniform sampler2D scene;
+uniform vec2 resolution;
+uniform float time;
+
+in vec2 uv;
+out vec4 outColour;
+"##;
+
+fn fragment_shader(effect: Effect) -> &'static str
+{
+    match effect {
+        Effect::Bloom => r##"
+        uniform vec3 apple;
+        uniform vec3 fleeingApple;
+
+        // How much a colour is the apple.
+        float glow(vec3 colour) {
+            float near = min(distance(colour, apple), distance(colour, fleeingApple));
+            return 1.0 - smoothstep(0.05, 0.2, near);
+        }
+
+        void main() {
+            vec3 colour = texture(scene, uv).rgb;
+
+            // The light of the apple spills over around it, less the further out.
+            vec2 spread = vec2(resolution.y / resolution.x, 1.0) * 0.006;
+            vec3 light  = vec3(0.0);
+            float total = 0.0;
+
+            for (int x = -4; x <= 4; x++) {
+                for (int y = -4; y <= 4; y++) {
+                    float weight   = exp(-float(x * x + y * y) / 8.0);
+                    vec3 neighbour = texture(scene, uv + vec2(x, y) * spread).rgb;
+
+                    light += neighbour * glow(neighbour) * weight;
+                    total += weight;
+                }
+            }
+
+            float strength = 1.5 + 0.4 * sin(time * 3.0);
+            outColour = vec4(colour + light / total * strength, 1.0);
+        }
+        "##,
+
+        Effect::ChromaticAberration => r##"
+        void main() {
+            // Red and blue drift apart towards the edges, like through a cheap lens.
+            vec2 offset = (uv - 0.5) * (0.006 + 0.002 * sin(time * 2.0));
+
+            outColour = vec4
+            (
+                texture(scene, uv + offset).r,
+                texture(scene, uv).g,
+                texture(scene, uv - offset).b,
+                1.0
+            );
+        }
+        "##,
+
+        Effect::Crt => r##"
+        void main() {
+            // Bulges out like the glass of an old screen.
+            vec2 centred = uv * 2.0 - 1.0;
+            centred     += centred * centred.yx * centred.yx * 0.08;
+            vec2 curved  = centred * 0.5 + 0.5;
+
+            if (any(lessThan(curved, vec2(0.0))) || any(greaterThan(curved, vec2(1.0)))) {
+                outColour = vec4(0.0, 0.0, 0.0, 1.0);
+                return;
+            }
+
+            // Dark lines between the rows, three pixels apart, slowly rolling down.
+            float scanline = 0.85 + 0.15 * sin(curved.y * resolution.y * 2.094 + time * 6.0);
+
+            outColour = vec4(texture(scene, curved).rgb * scanline, 1.0);
+        }
+        "##,
+
+        Effect::Vignette => r##"
+        void main() {
+            float edge = distance(uv, vec2(0.5));
+            outColour  = vec4(texture(scene, uv).rgb * (1.0 - 0.6 * smoothstep(0.4, 0.8, edge)), 1.0);
+        }
+        "##
+    }
+}
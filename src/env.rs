@@ -0,0 +1,301 @@
+use wasm_bindgen::prelude::*;
+
+use crate::Direction;
+use crate::bot::{blocked_cells, move_to};
+use crate::game::{Context, new_game, step, wrapped_distance};
+use crate::level::{Level, builtin_levels};
+
+/// Channels of the grid observation, in order.
+const CHANNELS: usize = 4;
+
+/// Length of the feature vector observation.
+const FEATURES: usize = 12;
+
+/// What the agent gets rewarded with. Every part of it is optional,
+/// so the reward can be shaped without touching the environment.
+#[wasm_bindgen]
+#[derive(Copy, Clone, Debug)]
+pub struct Rewards
+{
+    /// For every apple eaten.
+    pub apple: f64,
+    /// For dying, usually negative.
+    pub death: f64,
+    /// For every step taken, usually a small negative number so the agent hurries up.
+    pub step: f64,
+    /// For every cell the head gets closer to the apple, and taken away for every cell further.
+    pub approach: f64
+}
+
+#[wasm_bindgen]
+impl Rewards
+{
+    #[wasm_bindgen(constructor)]
+    pub fn new(apple: f64, death: f64, step: f64, approach: f64) -> Rewards
+    {
+        Rewards { apple, death, step, approach }
+    }
+}
+
+impl Default for Rewards
+{
+    fn default() -> Rewards
+    {
+        Rewards { apple: 1., death: -1., step: -0.01, approach: 0. }
+    }
+}
+
+/// What the agent sees of the game.
+#[wasm_bindgen]
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub enum ObservationKind
+{
+    /// The board as a tensor of channels by rows by columns, rows from the bottom.
+    /// The channels are the head, the rest of the snakes, the apple and the walls,
+    /// with a 1 on every cell that has it.
+    Grid,
+    /// A handful of numbers describing the situation around the head:
+    ///
+    /// - whether going straight, left or right would be deadly,
+    /// - which way the snake is going, one-hot in the order up, down, left, right,
+    /// - whether the apple is up, down, left or right of the head, taking the wraparound into account,
+    /// - how much of the board the snake takes up.
+    Features
+}
+
+/// The outcome of a single step.
+#[wasm_bindgen(getter_with_clone)]
+#[derive(Clone, Debug)]
+pub struct Step
+{
+    pub observation: Vec<f32>,
+    pub reward: f64,
+    /// The game is over, by dying, finishing the level or running out of ticks.
+    pub done: bool
+}
+
+/// The game as a reinforcement learning environment, in the style of Gym.
+///
+/// A single snake is played on the level, with an action every step.
+/// It's the same game core as the browser plays, without the browser,
+/// so it can be stepped as fast as the agent can keep up.
+#[wasm_bindgen]
+#[derive(Clone, Debug)]
+pub struct Environment
+{
+    level: Level,
+    ctx: Context,
+    kind: ObservationKind,
+    rewards: Rewards,
+
+    /// Games are cut short after this many ticks, so an agent that learned
+    /// to go around in circles doesn't play forever.
+    pub max_ticks: usize,
+    done: bool
+}
+
+impl Environment
+{
+    pub fn new(level: &Level, kind: ObservationKind, rewards: Rewards) -> Environment
+    {
+        Environment {
+            level: level.clone(),
+            ctx: new_game(level, 1, 0),
+            kind,
+            rewards,
+            max_ticks: 10_000,
+            done: false
+        }
+    }
+
+    /// The game being played, for anything the observation leaves out.
+    pub fn ctx(&self) -> &Context
+    {
+        &self.ctx
+    }
+
+    /// Starts a new game and returns the first observation.
+    pub fn reset(&mut self, seed: u64) -> Vec<f32>
+    {
+        self.ctx  = new_game(&self.level, 1, seed);
+        self.done = false;
+
+        self.observe()
+    }
+
+    /// Plays a single tick with `action` as the input. Nothing means not turning,
+    /// the same as pressing the key for the way the snake is already going.
+    ///
+    /// Stepping a game that's done changes nothing and gives no reward.
+    pub fn step(&mut self, action: Option<Direction>) -> Step
+    {
+        if self.done {
+            return Step { observation: self.observe(), reward: 0., done: true }
+        }
+
+        let before = self.apple_distance();
+        let events = step(&mut self.ctx, &[action]);
+
+        let mut reward = self.rewards.step;
+
+        if !events.died.is_empty() {
+            reward += self.rewards.death;
+            self.done = true;
+        }
+
+        if events.ate.is_some() {
+            reward += self.rewards.apple;
+        } else if let (Some(before), Some(after)) = (before, self.apple_distance()) {
+            reward += self.rewards.approach * (before as f64 - after as f64);
+        }
+
+        let snake = &self.ctx.snakes[0];
+
+        self.done |= self.ctx.target.is_some_and(|target| snake.score >= target)
+            || self.ctx.apple.is_none()
+            || self.ctx.ticks >= self.max_ticks;
+
+        Step { observation: self.observe(), reward, done: self.done }
+    }
+
+    /// The shape of every observation, outermost first.
+    pub fn shape(&self) -> Vec<usize>
+    {
+        match self.kind {
+            ObservationKind::Grid     => vec![CHANNELS, self.ctx.height, self.ctx.width],
+            ObservationKind::Features => vec![FEATURES]
+        }
+    }
+
+    fn observe(&self) -> Vec<f32>
+    {
+        match self.kind {
+            ObservationKind::Grid     => self.grid(),
+            ObservationKind::Features => self.features()
+        }
+    }
+
+    fn grid(&self) -> Vec<f32>
+    {
+        let ctx   = &self.ctx;
+        let cells = ctx.width * ctx.height;
+
+        let mut grid = vec![0.; CHANNELS * cells];
+        let mut set  = |channel: usize, (x, y): (usize, usize)| grid[channel * cells + y * ctx.width + x] = 1.;
+
+        for (i, snake) in ctx.snakes.iter().enumerate() {
+            for (j, &cell) in snake.body.iter().enumerate() {
+                set(if i == 0 && j == 0 { 0 } else { 1 }, cell);
+            }
+        }
+
+        if let Some(apple) = ctx.apple { set(2, apple.position); }
+
+        for &cell in &ctx.obstacles { set(3, cell); }
+
+        grid
+    }
+
+    fn features(&self) -> Vec<f32>
+    {
+        let ctx    = &self.ctx;
+        let snake  = &ctx.snakes[0];
+        let head   = snake.body[0];
+        let facing = snake.direction;
+
+        let blocked = blocked_cells(ctx);
+        let deadly  = |direction| move_to(ctx, &blocked, head, direction).is_none();
+
+        let (dx, dy) = ctx.apple.map_or((0, 0), |apple| self.offset(head, apple.position));
+
+        let flags = [
+            deadly(facing),
+            deadly(turn_left(facing)),
+            deadly(turn_left(facing).opposite()),
+            facing == Direction::Up,
+            facing == Direction::Down,
+            facing == Direction::Left,
+            facing == Direction::Right,
+            dy > 0,
+            dy < 0,
+            dx < 0,
+            dx > 0
+        ];
+
+        let mut features: Vec<f32> = flags.into_iter().map(|flag| flag as u8 as f32).collect();
+        features.push(snake.body.len() as f32 / (ctx.width * ctx.height) as f32);
+
+        features
+    }
+
+    /// The shortest way from one cell to another along each axis,
+    /// which can be across the board edge with wraparound on.
+    fn offset(&self, from: (usize, usize), to: (usize, usize)) -> (isize, isize)
+    {
+        let shortest = |from: usize, to: usize, size: usize| {
+            let d = to as isize - from as isize;
+
+            match self.ctx.wrap && d.unsigned_abs() * 2 > size {
+                true  => d - d.signum() * size as isize,
+                false => d
+            }
+        };
+
+        (shortest(from.0, to.0, self.ctx.width), shortest(from.1, to.1, self.ctx.height))
+    }
+
+    fn apple_distance(&self) -> Option<usize>
+    {
+        let apple = self.ctx.apple?;
+        Some(wrapped_distance(&self.ctx, self.ctx.snakes[0].body[0], apple.position))
+    }
+}
+
+/// The same environment for JavaScript. Actions are numbers there,
+/// 0 for not turning and 1 to 4 for up, down, left and right.
+#[wasm_bindgen]
+impl Environment
+{
+    /// An environment on one of the built-in levels, nothing if there's no such level.
+    pub fn builtin(level: usize, kind: ObservationKind, rewards: Rewards) -> Option<Environment>
+    {
+        builtin_levels().get(level).map(|level| Environment::new(level, kind, rewards))
+    }
+
+    #[wasm_bindgen(js_name = reset)]
+    pub fn reset_js(&mut self, seed: u32) -> Vec<f32>
+    {
+        self.reset(seed as u64)
+    }
+
+    #[wasm_bindgen(js_name = step)]
+    pub fn step_js(&mut self, action: u32) -> Step
+    {
+        let action = match action {
+            1 => Some(Direction::Up),
+            2 => Some(Direction::Down),
+            3 => Some(Direction::Left),
+            4 => Some(Direction::Right),
+            _ => None
+        };
+
+        self.step(action)
+    }
+
+    #[wasm_bindgen(js_name = shape)]
+    pub fn shape_js(&self) -> Vec<u32>
+    {
+        self.shape().into_iter().map(|size| size as u32).collect()
+    }
+}
+
+/// The direction a quarter turn anticlockwise.
+fn turn_left(direction: Direction) -> Direction
+{
+    match direction {
+        Direction::Up    => Direction::Left,
+        Direction::Left  => Direction::Down,
+        Direction::Down  => Direction::Right,
+        Direction::Right => Direction::Up
+    }
+}
@@ -0,0 +1,586 @@
+use crate::{Direction, SNAKE_STARTING_LEN};
+use crate::level::{Level, Terrain};
+use crate::rng::Rng;
+
+/// How many ticks a fleeing apple waits between moves.
+const APPLE_FLEE_INTERVAL: usize = 3;
+
+/// Free cells kept in front of the second snake when picking where it starts.
+const START_CLEARANCE: usize = 3;
+
+/// Grid coordinates, with (0, 0) being the bottom left corner.
+pub type Cell = (usize, usize);
+
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub enum AppleKind {
+    Still,
+    Fleeing
+}
+
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub struct Apple
+{
+    pub position: Cell,
+    pub kind: AppleKind,
+    pub spawned_at: usize,
+    pub lifetime: Option<usize>
+}
+
+impl Apple
+{
+    /// Fraction of the lifetime left, from 1 down to 0.
+    pub fn remaining(&self, ticks: usize) -> f32
+    {
+        let Some(lifetime) = self.lifetime else { return 1. };
+        let age = ticks - self.spawned_at;
+        1. - (age as f32 / lifetime as f32).min(1.)
+    }
+
+    pub fn expired(&self, ticks: usize) -> bool
+    {
+        self.lifetime.is_some_and(|lifetime| ticks - self.spawned_at >= lifetime)
+    }
+}
+
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub enum Death {
+    Body,
+    Obstacle,
+    Wall,
+    /// Ran into the body of another snake.
+    Snake,
+    /// Two heads ended up on the same cell.
+    HeadOn
+}
+
+/// What happened during a single step of the game.
+#[derive(Clone, Debug, Default)]
+pub struct Events
+{
+    /// Which snake ate the apple.
+    pub ate: Option<usize>,
+    pub apple_expired: bool,
+    /// Every snake that died, and what killed it.
+    pub died: Vec<(usize, Death)>
+}
+
+#[derive(Clone, Debug)]
+pub struct Snake
+{
+    /// Cells of the snake, head first.
+    pub body: Vec<Cell>,
+    /// Where every part of the snake was before the last step.
+    pub previous: Vec<Cell>,
+    /// The direction every part of the snake moved in to get to its cell.
+    pub headings: Vec<Direction>,
+    pub direction: Direction,
+
+    pub score: usize,
+    pub growth: usize
+}
+
+/// The whole state of a game. Holds no references to the browser,
+/// so it can be stepped natively as well.
+#[derive(Clone, Debug)]
+pub struct Context
+{
+    pub width: usize,
+    pub height: usize,
+
+    /// One snake per player, all moving at the same time.
+    pub snakes: Vec<Snake>,
+
+    pub apple: Option<Apple>,
+    pub obstacles: Vec<Cell>,
+    pub apple_spawns: Vec<Cell>,
+    pub portals: Vec<(Cell, Cell)>,
+    /// Terrain of every cell, row by row.
+    pub terrain: Vec<Option<Terrain>>,
+
+    pub wrap: bool,
+    pub target: Option<usize>,
+    pub apple_lifetime: Option<usize>,
+    pub fleeing_chance: f64,
+
+    /// Duration of a single step in milliseconds.
+    pub tick_duration: f64,
+    /// How the duration shrinks with every apple, and how far.
+    pub speed_up: f64,
+    pub fastest: f64,
+    pub ticks: usize,
+
+    pub rng: Rng
+}
+
+/// The part of a `Context` that changes as the game is played.
+/// Much cheaper to copy than the whole context, which also holds the level.
+#[derive(Clone, Debug)]
+pub struct Snapshot
+{
+    snakes: Vec<Snake>,
+    apple: Option<Apple>,
+    tick_duration: f64,
+    ticks: usize,
+    rng: Rng
+}
+
+impl Snapshot
+{
+    /// Hashes everything that affects how the game plays out (FNV-1a).
+    /// Two games that hash the same are in sync.
+    pub fn hash(&self) -> u64
+    {
+        let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+
+        let mut write = |value: u64| {
+            for byte in value.to_le_bytes() {
+                hash ^= byte as u64;
+                hash  = hash.wrapping_mul(0x0100_0000_01b3);
+            }
+        };
+
+        write(self.ticks as u64);
+        write(self.tick_duration.to_bits());
+        write(self.rng.clone().next_u64());
+
+        for snake in &self.snakes {
+            write(snake.body.len() as u64);
+            for &(x, y) in &snake.body {
+                write((x as u64) << 32 | y as u64);
+            }
+            write(snake.direction as u64);
+            write(snake.score as u64);
+            write(snake.growth as u64);
+        }
+
+        if let Some(apple) = self.apple {
+            write((apple.position.0 as u64) << 32 | apple.position.1 as u64);
+            write(apple.kind as u64);
+            write(apple.spawned_at as u64);
+        }
+
+        hash
+    }
+}
+
+pub fn snapshot(ctx: &Context) -> Snapshot
+{
+    Snapshot {
+        snakes: ctx.snakes.clone(),
+        apple: ctx.apple,
+        tick_duration: ctx.tick_duration,
+        ticks: ctx.ticks,
+        rng: ctx.rng.clone()
+    }
+}
+
+/// Puts the game back the way it was when the snapshot was taken.
+pub fn restore(ctx: &mut Context, snapshot: &Snapshot)
+{
+    ctx.snakes.clone_from(&snapshot.snakes);
+    ctx.apple         = snapshot.apple;
+    ctx.tick_duration = snapshot.tick_duration;
+    ctx.ticks         = snapshot.ticks;
+    ctx.rng           = snapshot.rng.clone();
+}
+
+/// Starts a game on the level for one or more players.
+/// The first snake starts where the level says. The second one starts
+/// on the opposite side of the board, going the opposite way, unless
+/// something's in the way there. See `second_start`.
+pub fn new_game(level: &Level, players: usize, seed: u64) -> Context
+{
+    let mut ctx = Context {
+        width: level.width,
+        height: level.height,
+        snakes: vec![],
+        apple: None,
+        obstacles: level.obstacles.clone(),
+        apple_spawns: level.apples.clone(),
+        portals: level.portals.clone(),
+        terrain: vec![None; level.width * level.height],
+        wrap: level.wrap,
+        target: level.target,
+        apple_lifetime: level.apple_lifetime,
+        fleeing_chance: level.fleeing,
+        tick_duration: level.speed,
+        speed_up: level.speed_up,
+        fastest: level.fastest,
+        ticks: 0,
+        rng: Rng::new(seed)
+    };
+
+    for &((x, y), terrain) in &level.terrain {
+        ctx.terrain[y * level.width + x] = Some(terrain);
+    }
+
+    let second = match players > 1 {
+        true  => second_start(&ctx, level),
+        false => (level.start, level.direction)
+    };
+
+    for player in 0..players {
+        let snake = match player % 2 {
+            0 => new_snake(&ctx, level.start, level.direction),
+            _ => new_snake(&ctx, second.0, second.1)
+        };
+
+        ctx.snakes.push(snake);
+    }
+
+    ctx.apple = spawn_apple(&mut ctx);
+
+    ctx
+}
+
+/// Where the second snake starts. The mirror of the first start when the
+/// snake fits there, otherwise the nearest straight run that's free of walls,
+/// portals, terrain and the first snake, with room in front to get going.
+fn second_start(ctx: &Context, level: &Level) -> (Cell, Direction)
+{
+    let mirror    = (level.width - 1 - level.start.0, level.height - 1 - level.start.1);
+    let preferred = level.direction.opposite();
+
+    let mut taken = new_snake(ctx, level.start, level.direction).body;
+    taken.extend(start_run(ctx, level.start, level.direction).unwrap_or_default());
+
+    let clear = |head: Cell, direction: Direction| {
+        start_run(ctx, head, direction).is_some_and(|run| {
+            run.iter().all(|cell| {
+                !taken.contains(cell)
+                    && !ctx.obstacles.contains(cell)
+                    && !ctx.portals.iter().any(|&(one, two)| *cell == one || *cell == two)
+                    && terrain_at(ctx, *cell).is_none()
+            })
+        })
+    };
+
+    let directions = [preferred, Direction::Up, Direction::Down, Direction::Left, Direction::Right];
+
+    let mut candidates: Vec<(Cell, Direction)> = (0..ctx.height)
+        .flat_map(|y| (0..ctx.width).map(move |x| (x, y)))
+        .flat_map(|cell| directions.map(|direction| (cell, direction)))
+        .collect();
+
+    // Closest to the mirror first, and going the preferred way where it's a tie.
+    candidates.sort_by_key(|&((x, y), direction)| (x.abs_diff(mirror.0) + y.abs_diff(mirror.1), direction != preferred));
+
+    candidates
+        .into_iter()
+        .find(|&(head, direction)| clear(head, direction))
+        .unwrap_or((mirror, preferred))
+}
+
+/// The cells a snake starting at `head` takes up, and the ones in front of it
+/// it needs to get going. Nothing if they don't fit on a board without wraparound.
+fn start_run(ctx: &Context, head: Cell, direction: Direction) -> Option<Vec<Cell>>
+{
+    let (step_x, step_y) = direction.offset();
+
+    (-(START_CLEARANCE as isize)..SNAKE_STARTING_LEN as isize)
+        .map(|i| {
+            let x = head.0 as isize - step_x * i;
+            let y = head.1 as isize - step_y * i;
+
+            let inside = x >= 0 && y >= 0 && x < ctx.width as isize && y < ctx.height as isize;
+
+            match inside || ctx.wrap {
+                true  => Some((x.rem_euclid(ctx.width as isize) as usize, y.rem_euclid(ctx.height as isize) as usize)),
+                false => None
+            }
+        })
+        .collect()
+}
+
+fn new_snake(ctx: &Context, start: Cell, direction: Direction) -> Snake
+{
+    let mut body = Vec::with_capacity(SNAKE_STARTING_LEN);
+
+    // The body trails behind the head, opposite to the starting direction.
+    let (step_x, step_y) = direction.opposite().offset();
+
+    for i in 0..SNAKE_STARTING_LEN as isize {
+        let x = start.0 as isize + step_x * i;
+        let y = start.1 as isize + step_y * i;
+
+        // Without wraparound, whatever doesn't fit on the board is
+        // stacked on the last part and unfolds as the snake moves.
+        let inside = x >= 0 && y >= 0 && x < ctx.width as isize && y < ctx.height as isize;
+
+        let part = match inside || ctx.wrap {
+            true  => (x.rem_euclid(ctx.width as isize) as usize, y.rem_euclid(ctx.height as isize) as usize),
+            false => *body.last().unwrap()
+        };
+
+        body.push(part);
+    }
+
+    Snake {
+        previous: body.clone(),
+        body,
+        headings: vec![direction; SNAKE_STARTING_LEN],
+        direction,
+        score: 0,
+        growth: 0
+    }
+}
+
+/// Moves every snake by a single cell, with `inputs` holding the input
+/// of every player in order. Turning around onto itself is ignored.
+///
+/// Snakes move at the same time, so a snake can follow right behind
+/// the tail of another one. Running into the body of another snake only
+/// kills the one that ran into it, while meeting head on kills both.
+pub fn step(ctx: &mut Context, inputs: &[Option<Direction>]) -> Events
+{
+    let mut events = Events::default();
+
+    let heads: Vec<Option<Cell>> = (0..ctx.snakes.len())
+        .map(|i| match next_head(ctx, i, inputs.get(i).copied().flatten()) {
+            Ok(head) => Some(head),
+            Err(death) => {
+                events.died.push((i, death));
+                None
+            }
+        })
+        .collect();
+
+    for (snake, head) in ctx.snakes.iter_mut().zip(&heads) {
+        snake.previous = snake.body.clone();
+
+        let Some(head) = *head else { continue };
+
+        snake.body.insert(0, head);
+        snake.headings.insert(0, snake.direction);
+
+        // A growing snake leaves its tail where it was for a step.
+        match snake.growth {
+            0 => {
+                snake.body.pop();
+                snake.headings.pop();
+            }
+            _ => {
+                snake.growth -= 1;
+                snake.previous.push(*snake.previous.last().unwrap());
+            }
+        }
+    }
+
+    for (i, head) in heads.iter().enumerate() {
+        let Some(head) = *head else { continue };
+
+        for (j, other) in ctx.snakes.iter().enumerate() {
+            let death = match (i == j, other.body[0] == head) {
+                (true, _) if other.body[1..].contains(&head) => Death::Body,
+                (false, true) => Death::HeadOn,
+                (false, false) if other.body[1..].contains(&head) => Death::Snake,
+                _ => continue
+            };
+
+            events.died.push((i, death));
+            break
+        }
+    }
+
+    if !events.died.is_empty() { return events }
+
+    ctx.ticks += 1;
+
+    let eater = ctx.snakes
+        .iter()
+        .position(|snake| ctx.apple.is_some_and(|apple| apple.position == snake.body[0]));
+
+    if let Some(i) = eater {
+        ctx.snakes[i].score  += 1;
+        ctx.snakes[i].growth += 1;
+        ctx.tick_duration     = (ctx.tick_duration * ctx.speed_up).max(ctx.fastest);
+        ctx.apple             = spawn_apple(ctx);
+        events.ate            = Some(i);
+    }
+
+    events.apple_expired = apple_tick(ctx);
+
+    events
+}
+
+/// The last snake standing once the snakes in `died` are gone.
+/// Nothing if none or more than one are left.
+pub fn survivor(ctx: &Context, died: &[(usize, Death)]) -> Option<usize>
+{
+    let mut alive = (0..ctx.snakes.len()).filter(|i| died.iter().all(|(dead, _)| dead != i));
+
+    match (alive.next(), alive.next()) {
+        (Some(survivor), None) => Some(survivor),
+        _ => None
+    }
+}
+
+/// Turns a snake according to the input and the terrain under it,
+/// and returns where its head moves to next.
+fn next_head(ctx: &mut Context, i: usize, input: Option<Direction>) -> Result<Cell, Death>
+{
+    let snake = &ctx.snakes[i];
+
+    // The terrain under the head can take the steering away.
+    let input = match terrain_at(ctx, snake.body[0]) {
+        Some(Terrain::Ice)                 => None,
+        Some(Terrain::Conveyor(direction)) => Some(direction),
+        _ => input
+    };
+
+    let direction = input
+        .filter(|input| *input != snake.direction.opposite())
+        .unwrap_or(snake.direction);
+
+    ctx.snakes[i].direction = direction;
+
+    step_into(ctx, ctx.snakes[i].body[0], direction)
+}
+
+/// The cell a step from `cell` going `direction` ends up on, or what
+/// gets in the way. Other snakes are left to the caller.
+pub fn step_into(ctx: &Context, cell: Cell, direction: Direction) -> Result<Cell, Death>
+{
+    let head = next_cell(ctx, cell, direction).ok_or(Death::Wall)?;
+
+    if ctx.obstacles.contains(&head) { return Err(Death::Obstacle) }
+
+    // One-way gates are walls from every other side. A belt running against
+    // the snake would fold it back onto itself, so that side is a wall too.
+    match terrain_at(ctx, head) {
+        Some(Terrain::Gate(gate)) if gate != direction => Err(Death::Obstacle),
+        Some(Terrain::Conveyor(belt)) if belt == direction.opposite() => Err(Death::Obstacle),
+        _ => Ok(head)
+    }
+}
+
+/// How long the next step takes, in milliseconds.
+/// Snakes move together, so a single one in mud slows down all of them.
+pub fn step_duration(ctx: &Context) -> f64
+{
+    let in_mud = ctx.snakes
+        .iter()
+        .any(|snake| terrain_at(ctx, snake.body[0]) == Some(Terrain::Mud));
+
+    match in_mud {
+        true  => ctx.tick_duration * 2.,
+        false => ctx.tick_duration
+    }
+}
+
+pub fn terrain_at(ctx: &Context, (x, y): Cell) -> Option<Terrain>
+{
+    ctx.terrain[y * ctx.width + x]
+}
+
+/// Returns the cell reached by moving from `cell` in `direction`,
+/// or nothing if the move goes into a wall at the board edge.
+pub fn next_cell(ctx: &Context, cell: Cell, direction: Direction) -> Option<Cell>
+{
+    let (x, y) = direction.offset();
+    teleport(ctx, (cell.0 as isize + x, cell.1 as isize + y))
+}
+
+/// Moving onto a portal puts the snake on its partner instead.
+/// Board edges behave the same way when wrapping is on: stepping off the
+/// board is stepping into a portal that leads to the opposite edge.
+fn teleport(ctx: &Context, (x, y): (isize, isize)) -> Option<Cell>
+{
+    let outside = x < 0 || y < 0 || x >= ctx.width as isize || y >= ctx.height as isize;
+    if outside && !ctx.wrap { return None }
+
+    let cell = (x.rem_euclid(ctx.width as isize) as usize, y.rem_euclid(ctx.height as isize) as usize);
+
+    for &(one, two) in &ctx.portals {
+        if cell == one { return Some(two) }
+        if cell == two { return Some(one) }
+    }
+
+    Some(cell)
+}
+
+pub fn occupied(ctx: &Context, cell: Cell) -> bool
+{
+    ctx.snakes.iter().any(|snake| snake.body.contains(&cell))
+        || ctx.obstacles.contains(&cell)
+        || ctx.portals.iter().any(|&(one, two)| cell == one || cell == two)
+}
+
+/// Puts a new apple on a free cell, or nothing if the board is full.
+pub fn spawn_apple(ctx: &mut Context) -> Option<Apple>
+{
+    // Levels with fixed apple spots only spawn apples there, as long as one is free.
+    let mut unoccupied: Vec<Cell> = ctx.apple_spawns
+        .iter()
+        .copied()
+        .filter(|&cell| !occupied(ctx, cell))
+        .collect();
+
+    if unoccupied.is_empty() {
+        for x in 0..ctx.width {
+            for y in 0..ctx.height {
+                if !occupied(ctx, (x, y)) { unoccupied.push((x, y)); }
+            }
+        }
+    }
+
+    if unoccupied.is_empty() { return None }
+
+    let position = unoccupied[ctx.rng.below(unoccupied.len())];
+
+    let kind = match ctx.rng.chance(ctx.fleeing_chance) {
+        true  => AppleKind::Fleeing,
+        false => AppleKind::Still
+    };
+
+    Some(Apple { position, kind, spawned_at: ctx.ticks, lifetime: ctx.apple_lifetime })
+}
+
+/// Runs once per step. Expires old apples and moves the fleeing ones.
+/// Returns true if the apple expired.
+fn apple_tick(ctx: &mut Context) -> bool
+{
+    let Some(mut apple) = ctx.apple else { return false };
+
+    if apple.expired(ctx.ticks) {
+        ctx.apple = spawn_apple(ctx);
+        return true
+    }
+
+    if apple.kind != AppleKind::Fleeing { return false }
+    if !(ctx.ticks - apple.spawned_at).is_multiple_of(APPLE_FLEE_INTERVAL) { return false }
+
+    // Runs from whichever head is closest.
+    let distance = |cell: Cell| ctx.snakes
+        .iter()
+        .map(|snake| wrapped_distance(ctx, snake.body[0], cell))
+        .min()
+        .unwrap_or(0);
+
+    let mut best = (apple.position, distance(apple.position));
+
+    for direction in [Direction::Up, Direction::Down, Direction::Left, Direction::Right] {
+        let Some(cell) = next_cell(ctx, apple.position, direction) else { continue };
+        if occupied(ctx, cell) { continue }
+
+        let distance = distance(cell);
+        if distance > best.1 { best = (cell, distance); }
+    }
+
+    apple.position = best.0;
+    ctx.apple      = Some(apple);
+
+    false
+}
+
+/// Manhattan distance between two cells, taking the wraparound into account.
+pub fn wrapped_distance(ctx: &Context, one: Cell, two: Cell) -> usize
+{
+    let dx = one.0.abs_diff(two.0);
+    let dy = one.1.abs_diff(two.1);
+
+    match ctx.wrap {
+        true  => dx.min(ctx.width - dx) + dy.min(ctx.height - dy),
+        false => dx + dy
+    }
+}
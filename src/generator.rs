@@ -0,0 +1,313 @@
+use std::collections::VecDeque;
+
+use crate::{Direction, SNAKE_STARTING_LEN};
+use crate::game::{new_game, step_into};
+use crate::level::{Level, SPEED_UP};
+use crate::rng::Rng;
+
+/// How many free cells ahead of the starting snake are always kept clear.
+const START_CLEARANCE: usize = 3;
+
+/// A generated level is thrown away if fewer than this fraction of cells is free.
+const MIN_FREE_FRACTION: f64 = 0.5;
+
+const MAX_ATTEMPTS: u64 = 32;
+
+/// The smallest board generated, with room for the snake and the cells ahead of it.
+const MIN_WIDTH: usize  = SNAKE_STARTING_LEN + START_CLEARANCE;
+const MIN_HEIGHT: usize = 3;
+
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub enum Layout {
+    Maze,
+    Rooms,
+    Pillars,
+    Arena
+}
+
+impl Layout
+{
+    pub fn from_name(name: &str) -> Option<Layout>
+    {
+        match name {
+            "maze"    => Some(Layout::Maze),
+            "rooms"   => Some(Layout::Rooms),
+            "pillars" => Some(Layout::Pillars),
+            "arena"   => Some(Layout::Arena),
+            _ => None
+        }
+    }
+
+    fn name(self) -> &'static str
+    {
+        match self {
+            Layout::Maze    => "Maze",
+            Layout::Rooms   => "Rooms",
+            Layout::Pillars => "Pillars",
+            Layout::Arena   => "Arena"
+        }
+    }
+}
+
+/// Generates a wraparound level for the given board size.
+/// The same layout, size and seed always give the same level.
+///
+/// Every free cell of the result is reachable from the snake start,
+/// and the starting snake always has room to move. Smaller boards than
+/// `MIN_WIDTH` by `MIN_HEIGHT` come out at that size.
+pub fn generate_level(layout: Layout, width: usize, height: usize, seed: u64) -> Level
+{
+    let width  = width.max(MIN_WIDTH);
+    let height = height.max(MIN_HEIGHT);
+    let start  = (width / 2, height / 2);
+
+    for attempt in 0..MAX_ATTEMPTS {
+        let mut rng   = Rng::new(seed.wrapping_add(attempt.wrapping_mul(0x1000_0000_01B3)));
+        let mut walls = vec![false; width * height];
+
+        match layout {
+            Layout::Maze    => maze(&mut walls, width, height, &mut rng),
+            Layout::Rooms   => rooms(&mut walls, width, height, &mut rng),
+            Layout::Pillars => pillars(&mut walls, width, height, &mut rng),
+            Layout::Arena   => arena(&mut walls, width, height, &mut rng),
+        }
+
+        // The snake starts going left, with the body trailing to the right.
+        for i in 0..SNAKE_STARTING_LEN + START_CLEARANCE {
+            let x = (start.0 + width + i - START_CLEARANCE) % width;
+            walls[start.1 * width + x] = false;
+        }
+
+        // Walls off the pockets the snake can't get to.
+        let reachable = flood_fill(&walls, width, height, start);
+        for (wall, reachable) in walls.iter_mut().zip(&reachable) {
+            if !reachable { *wall = true; }
+        }
+
+        let free = walls.iter().filter(|wall| !**wall).count();
+        if (free as f64) < (width * height) as f64 * MIN_FREE_FRACTION { continue }
+
+        return Level {
+            name: format!("{} #{}", layout.name(), seed),
+            width,
+            height,
+            wrap: true,
+            speed: 220.,
+            speed_up: SPEED_UP,
+            fastest: 0.,
+            target: None,
+            apple_lifetime: None,
+            fleeing: 0.,
+            start,
+            direction: Direction::Left,
+            obstacles: (0..width * height)
+                .filter(|&i| walls[i])
+                .map(|i| (i % width, i / width))
+                .collect(),
+            apples: vec![],
+            portals: vec![],
+            terrain: vec![]
+        }
+    }
+
+    // Nothing usable came out, which only happens on tiny boards.
+    Level {
+        name: format!("{} #{}", layout.name(), seed),
+        width,
+        height,
+        wrap: true,
+        speed: 220.,
+        speed_up: SPEED_UP,
+        fastest: 0.,
+        target: None,
+        apple_lifetime: None,
+        fleeing: 0.,
+        start,
+        direction: Direction::Left,
+        obstacles: vec![],
+        apples: vec![],
+        portals: vec![],
+        terrain: vec![]
+    }
+}
+
+/// Checks that every free cell of the level can be reached from the snake start,
+/// moving the way the snake does: through the edges only when they wrap,
+/// through portals, and into gates and onto belts only from the open side.
+pub fn fully_reachable(level: &Level) -> bool
+{
+    let ctx = new_game(level, 1, 0);
+
+    let mut reachable = vec![false; level.width * level.height];
+    let mut queue     = VecDeque::from([level.start]);
+    reachable[level.start.1 * level.width + level.start.0] = true;
+
+    while let Some(cell) = queue.pop_front() {
+        for direction in [Direction::Up, Direction::Down, Direction::Left, Direction::Right] {
+            let Ok((x, y)) = step_into(&ctx, cell, direction) else { continue };
+            if reachable[y * level.width + x] { continue }
+
+            reachable[y * level.width + x] = true;
+            queue.push_back((x, y));
+        }
+    }
+
+    // Portals are never stood on, the snake comes out of the partner instead.
+    (0..level.height)
+        .flat_map(|y| (0..level.width).map(move |x| (x, y)))
+        .filter(|cell| !level.obstacles.contains(cell) && !level.portals.iter().any(|&(one, two)| *cell == one || *cell == two))
+        .all(|(x, y)| reachable[y * level.width + x])
+}
+
+/// Marks every cell reachable from `start`, moving through the board edges.
+fn flood_fill(walls: &[bool], width: usize, height: usize, start: (usize, usize)) -> Vec<bool>
+{
+    let mut reachable = vec![false; width * height];
+    let mut queue     = VecDeque::new();
+
+    if walls[start.1 * width + start.0] { return reachable }
+
+    reachable[start.1 * width + start.0] = true;
+    queue.push_back(start);
+
+    while let Some((x, y)) = queue.pop_front() {
+        let neighbours = [
+            ((x + 1) % width, y),
+            ((x + width - 1) % width, y),
+            (x, (y + 1) % height),
+            (x, (y + height - 1) % height),
+        ];
+
+        for (x, y) in neighbours {
+            let index = y * width + x;
+            if walls[index] || reachable[index] { continue }
+
+            reachable[index] = true;
+            queue.push_back((x, y));
+        }
+    }
+
+    reachable
+}
+
+/// Recursive backtracker over every other cell, with extra walls knocked
+/// out afterwards. Perfect mazes are full of dead ends, which a snake hates.
+fn maze(walls: &mut [bool], width: usize, height: usize, rng: &mut Rng)
+{
+    let cells_x = width / 2;
+    let cells_y = height / 2;
+    if cells_x == 0 || cells_y == 0 { return }
+
+    // Passages are on even coordinates, walls fill the rest of the lattice.
+    for y in 0..cells_y * 2 {
+        for x in 0..cells_x * 2 {
+            walls[y * width + x] = x % 2 == 1 || y % 2 == 1;
+        }
+    }
+
+    let mut visited = vec![false; cells_x * cells_y];
+    let mut stack   = vec![(rng.below(cells_x), rng.below(cells_y))];
+    visited[stack[0].1 * cells_x + stack[0].0] = true;
+
+    while let Some(&(x, y)) = stack.last() {
+        let mut neighbours = Vec::with_capacity(4);
+        if x > 0           && !visited[y * cells_x + x - 1] { neighbours.push((x - 1, y)); }
+        if x + 1 < cells_x && !visited[y * cells_x + x + 1] { neighbours.push((x + 1, y)); }
+        if y > 0           && !visited[(y - 1) * cells_x + x] { neighbours.push((x, y - 1)); }
+        if y + 1 < cells_y && !visited[(y + 1) * cells_x + x] { neighbours.push((x, y + 1)); }
+
+        if neighbours.is_empty() {
+            stack.pop();
+            continue
+        }
+
+        let (next_x, next_y) = neighbours[rng.below(neighbours.len())];
+        walls[(y + next_y) * width + (x + next_x)] = false;
+
+        visited[next_y * cells_x + next_x] = true;
+        stack.push((next_x, next_y));
+    }
+
+    // Braid the maze by opening up some of the remaining walls between passages.
+    for y in 0..cells_y * 2 {
+        for x in 0..cells_x * 2 {
+            let between_passages = (x % 2 == 1) != (y % 2 == 1);
+            if between_passages && rng.chance(0.3) {
+                walls[y * width + x] = false;
+            }
+        }
+    }
+}
+
+/// Splits the board into rooms, with at least one door in every wall.
+fn rooms(walls: &mut [bool], width: usize, height: usize, rng: &mut Rng)
+{
+    let room_width  = 4 + rng.below(3);
+    let room_height = 3 + rng.below(3);
+
+    for x in (room_width..width).step_by(room_width + 1) {
+        for y in 0..height { walls[y * width + x] = true; }
+    }
+
+    for y in (room_height..height).step_by(room_height + 1) {
+        for x in 0..width { walls[y * width + x] = true; }
+    }
+
+    // Doors in the vertical walls, one per room.
+    for x in (room_width..width).step_by(room_width + 1) {
+        for y in (0..height).step_by(room_height + 1) {
+            let door = y + rng.below(room_height.min(height - y));
+            walls[door * width + x] = false;
+        }
+    }
+
+    // And in the horizontal ones.
+    for y in (room_height..height).step_by(room_height + 1) {
+        for x in (0..width).step_by(room_width + 1) {
+            let door = x + rng.below(room_width.min(width - x));
+            walls[y * width + door] = false;
+        }
+    }
+}
+
+/// Single pillars spread over the board, never touching each other,
+/// so they can't close off any cells.
+fn pillars(walls: &mut [bool], width: usize, height: usize, rng: &mut Rng)
+{
+    let count = width * height / 10;
+
+    for _ in 0..count {
+        let x = rng.below(width);
+        let y = rng.below(height);
+
+        let touching = (x.saturating_sub(1)..=(x + 1).min(width - 1))
+            .flat_map(|x| (y.saturating_sub(1)..=(y + 1).min(height - 1)).map(move |y| (x, y)))
+            .any(|(x, y)| walls[y * width + x]);
+
+        if !touching { walls[y * width + x] = true; }
+    }
+}
+
+/// Short wall segments in one quarter of the board, mirrored into the other three.
+fn arena(walls: &mut [bool], width: usize, height: usize, rng: &mut Rng)
+{
+    let quarter_width  = width.div_ceil(2);
+    let quarter_height = height.div_ceil(2);
+
+    for _ in 0..2 + rng.below(3) {
+        let mut x = rng.below(quarter_width);
+        let mut y = rng.below(quarter_height);
+        let horizontal = rng.chance(0.5);
+
+        for _ in 0..2 + rng.below(3) {
+            for (mirror_x, mirror_y) in [(x, y), (width - 1 - x, y), (x, height - 1 - y), (width - 1 - x, height - 1 - y)] {
+                walls[mirror_y * width + mirror_x] = true;
+            }
+
+            match horizontal {
+                true  => x = (x + 1).min(quarter_width - 1),
+                false => y = (y + 1).min(quarter_height - 1)
+            }
+        }
+    }
+}
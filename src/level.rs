@@ -0,0 +1,403 @@
+use std::fmt;
+
+use wasm_bindgen::prelude::*;
+
+use crate::{Direction, SNAKE_STARTING_LEN};
+
+/// How much faster the snake gets with every apple, unless the level says otherwise.
+pub(crate) const SPEED_UP: f64 = 0.975;
+
+/// Static layout of the board the snake plays on.
+/// Cells are in grid coordinates, with (0, 0) being the bottom left corner.
+///
+/// Levels are written in a plain text format. A header of `key: value`
+/// lines is followed by an empty line and the grid itself, top row first:
+///
+/// ```text
+/// name: Fortress
+/// edges: walls
+/// speed: 180
+/// speedup: 0.95
+/// fastest: 90
+/// target: 15
+/// lifetime: 40
+/// fleeing: 0.25
+/// direction: left
+///
+/// ######..######
+/// #....A.......#
+/// #..P....S..P.#
+/// ######..######
+/// ```
+///
+/// `speed` is how long a step takes at first, in milliseconds. Every apple
+/// multiplies it by `speedup`, until it's down to `fastest`.
+/// `target` is the number of apples that completes the level. A `length`
+/// header can be used instead to set the target as the length of the snake.
+/// `lifetime` is how many ticks apples last, and `fleeing` the chance
+/// of an apple running away from the snake.
+///
+/// Grid legend: `#` wall, `.` empty, `S` snake head, `A` fixed apple spot,
+/// `P` portal. Portals are paired in reading order. There is exactly one
+/// `S`, and the body trailing behind it can't start on a wall or portal.
+///
+/// Terrain: `~` ice, `%` mud, `<` `>` `^` `v` conveyors pushing the snake
+/// that way, `n` `e` `s` `w` one-way gates that can only be entered
+/// going north (up), east (right), south (down) or west (left).
+#[derive(Clone, Debug)]
+pub struct Level
+{
+    pub name: String,
+    pub width: usize,
+    pub height: usize,
+
+    /// Whether leaving the board wraps around to the other side.
+    /// If not, the board edges kill the snake.
+    pub wrap: bool,
+
+    /// Duration of a single snake step in milliseconds.
+    pub speed: f64,
+    /// The step duration is multiplied by this every time an apple is eaten.
+    pub speed_up: f64,
+    /// The shortest the step duration gets, in milliseconds.
+    pub fastest: f64,
+    pub target: Option<usize>,
+
+    pub apple_lifetime: Option<usize>,
+    pub fleeing: f64,
+
+    pub start: (usize, usize),
+    pub direction: Direction,
+
+    pub obstacles: Vec<(usize, usize)>,
+    pub apples: Vec<(usize, usize)>,
+    pub portals: Vec<((usize, usize), (usize, usize))>,
+    pub terrain: Vec<((usize, usize), Terrain)>
+}
+
+impl Level
+{
+    /// Writes the level out in the text format read by `parse_level`.
+    /// Portals are paired in reading order, the same way they are read back.
+    pub fn to_text(&self) -> String
+    {
+        let mut grid = vec!['.'; self.width * self.height];
+
+        for &(x, y) in &self.obstacles { grid[y * self.width + x] = '#'; }
+        for &(x, y) in &self.apples { grid[y * self.width + x] = 'A'; }
+        for &((x, y), terrain) in &self.terrain { grid[y * self.width + x] = terrain.symbol(); }
+        for &(one, two) in &self.portals {
+            grid[one.1 * self.width + one.0] = 'P';
+            grid[two.1 * self.width + two.0] = 'P';
+        }
+        grid[self.start.1 * self.width + self.start.0] = 'S';
+
+        write_level(self, &grid)
+    }
+}
+
+/// Writes the header of `level` and then `grid`, which holds a tile symbol
+/// for every cell, row by row from the bottom. Only the header is taken from
+/// the level, so the editor can write out a grid that isn't a valid level yet
+/// and have `parse_level` point out what's wrong with it.
+pub(crate) fn write_level(level: &Level, grid: &[char]) -> String
+{
+    let mut text = String::new();
+
+    text.push_str(&format!("name: {}\n", level.name));
+    text.push_str(&format!("edges: {}\n", if level.wrap { "wrap" } else { "walls" }));
+    text.push_str(&format!("speed: {}\n", level.speed));
+    if level.speed_up != SPEED_UP {
+        text.push_str(&format!("speedup: {}\n", level.speed_up));
+    }
+    if level.fastest > 0. {
+        text.push_str(&format!("fastest: {}\n", level.fastest));
+    }
+    if let Some(target) = level.target {
+        text.push_str(&format!("target: {}\n", target));
+    }
+    if let Some(lifetime) = level.apple_lifetime {
+        text.push_str(&format!("lifetime: {}\n", lifetime));
+    }
+    if level.fleeing > 0. {
+        text.push_str(&format!("fleeing: {}\n", level.fleeing));
+    }
+    text.push_str(&format!("direction: {}\n", level.direction.name()));
+    text.push('\n');
+
+    for y in (0..level.height).rev() {
+        text.extend(&grid[y * level.width..(y + 1) * level.width]);
+        text.push('\n');
+    }
+
+    text
+}
+
+/// Cells that change how the snake moves over them.
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub enum Terrain {
+    /// The snake can't turn while its head is on ice.
+    Ice,
+    /// Steps take twice as long while the head is in mud.
+    Mud,
+    /// Pushes the snake in the given direction on its next step.
+    /// Can't be entered against the belt.
+    Conveyor(Direction),
+    /// Can only be entered moving in the given direction.
+    Gate(Direction)
+}
+
+impl Terrain
+{
+    pub fn from_symbol(symbol: char) -> Option<Terrain>
+    {
+        match symbol {
+            '~' => Some(Terrain::Ice),
+            '%' => Some(Terrain::Mud),
+            '^' => Some(Terrain::Conveyor(Direction::Up)),
+            'v' => Some(Terrain::Conveyor(Direction::Down)),
+            '<' => Some(Terrain::Conveyor(Direction::Left)),
+            '>' => Some(Terrain::Conveyor(Direction::Right)),
+            'n' => Some(Terrain::Gate(Direction::Up)),
+            's' => Some(Terrain::Gate(Direction::Down)),
+            'w' => Some(Terrain::Gate(Direction::Left)),
+            'e' => Some(Terrain::Gate(Direction::Right)),
+            _ => None
+        }
+    }
+
+    pub fn symbol(self) -> char
+    {
+        match self {
+            Terrain::Ice                        => '~',
+            Terrain::Mud                        => '%',
+            Terrain::Conveyor(Direction::Up)    => '^',
+            Terrain::Conveyor(Direction::Down)  => 'v',
+            Terrain::Conveyor(Direction::Left)  => '<',
+            Terrain::Conveyor(Direction::Right) => '>',
+            Terrain::Gate(Direction::Up)        => 'n',
+            Terrain::Gate(Direction::Down)      => 's',
+            Terrain::Gate(Direction::Left)      => 'w',
+            Terrain::Gate(Direction::Right)     => 'e'
+        }
+    }
+}
+
+#[wasm_bindgen(getter_with_clone)]
+#[derive(Clone, Debug)]
+pub struct LevelError
+{
+    pub line: usize,
+    pub column: usize,
+    pub message: String
+}
+
+impl fmt::Display for LevelError
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+    {
+        write!(f, "line {}, column {}: {}", self.line, self.column, self.message)
+    }
+}
+
+fn error(line: usize, column: usize, message: impl Into<String>) -> LevelError
+{
+    LevelError { line, column, message: message.into() }
+}
+
+/// The levels shipped with the game. The first one is the classic empty board.
+pub fn builtin_levels() -> Vec<Level>
+{
+    [
+        include_str!("../levels/open_field.txt"),
+        include_str!("../levels/pillars.txt"),
+        include_str!("../levels/corridors.txt"),
+        include_str!("../levels/fortress.txt"),
+        include_str!("../levels/portals.txt"),
+        include_str!("../levels/terrain.txt"),
+    ]
+        .into_iter()
+        .map(|source| parse_level(source).expect("Built-in level failed to parse"))
+        .collect()
+}
+
+pub fn parse_level(source: &str) -> Result<Level, LevelError>
+{
+    let mut lines = source.lines().enumerate().map(|(i, line)| (i + 1, line));
+
+    let mut name      = String::from("Untitled");
+    let mut wrap      = true;
+    let mut speed     = 220.;
+    let mut speed_up  = SPEED_UP;
+    let mut fastest   = 0.;
+    let mut target    = None;
+    let mut lifetime  = None;
+    let mut fleeing   = 0.;
+    let mut direction = Direction::Left;
+
+    // Header
+
+    for (line_number, line) in lines.by_ref() {
+        let line = line.trim_end();
+        if line.is_empty() { break }
+
+        let Some((key, value)) = line.split_once(':') else {
+            return Err(error(line_number, 1, "Expected a `key: value` header line"))
+        };
+
+        let value_column = key.len() + 2 + (value.len() - value.trim_start().len());
+        let value        = value.trim();
+
+        match key.trim() {
+            "name"   => name = value.to_string(),
+            "edges"  => wrap = match value {
+                "wrap"  => true,
+                "walls" => false,
+                _ => return Err(error(line_number, value_column, "Edges must be either `wrap` or `walls`"))
+            },
+            "speed"  => speed = match value.parse::<f64>() {
+                Ok(speed) if speed > 0. && speed.is_finite() => speed,
+                _ => return Err(error(line_number, value_column, "Speed must be a positive number of milliseconds"))
+            },
+            "speedup" => speed_up = match value.parse::<f64>() {
+                Ok(factor) if factor > 0. && factor.is_finite() => factor,
+                _ => return Err(error(line_number, value_column, "Speedup must be a positive number, below 1 to speed up"))
+            },
+            "fastest" => fastest = match value.parse::<f64>() {
+                Ok(fastest) if fastest >= 0. && fastest.is_finite() => fastest,
+                _ => return Err(error(line_number, value_column, "Fastest must be a number of milliseconds"))
+            },
+            "target" => target = match value.parse::<usize>() {
+                Ok(target) => Some(target),
+                Err(_) => return Err(error(line_number, value_column, "Target must be a whole number"))
+            },
+            "length" => target = match value.parse::<usize>() {
+                Ok(length) if length > SNAKE_STARTING_LEN => Some(length - SNAKE_STARTING_LEN),
+                _ => return Err(error(line_number, value_column, format!("Length must be a whole number above {}", SNAKE_STARTING_LEN)))
+            },
+            "lifetime" => lifetime = match value.parse::<usize>() {
+                Ok(0) => None,
+                Ok(ticks) => Some(ticks),
+                Err(_) => return Err(error(line_number, value_column, "Lifetime must be a whole number of ticks"))
+            },
+            "fleeing" => fleeing = match value.parse::<f64>() {
+                Ok(chance) if (0. ..=1.).contains(&chance) => chance,
+                _ => return Err(error(line_number, value_column, "Fleeing must be a chance between 0 and 1"))
+            },
+            "direction" => direction = match Direction::from_name(value) {
+                Some(direction) => direction,
+                None => return Err(error(line_number, value_column, "Direction must be one of `up`, `down`, `left`, `right`"))
+            },
+            key => return Err(error(line_number, 1, format!("Unknown header `{}`", key)))
+        }
+    }
+
+    // Grid
+
+    let rows: Vec<(usize, &str)> = lines
+        .map(|(line_number, line)| (line_number, line.trim_end()))
+        .filter(|(_, line)| !line.is_empty())
+        .collect();
+
+    let Some(&(first_line, first_row)) = rows.first() else {
+        return Err(error(source.lines().count() + 1, 1, "Level has no grid"))
+    };
+
+    let width  = first_row.chars().count();
+    let height = rows.len();
+
+    let mut start        = None;
+    let mut obstacles    = vec![];
+    let mut apples       = vec![];
+    let mut portal_cells = vec![];
+    let mut terrain      = vec![];
+
+    for (row, &(line_number, line)) in rows.iter().enumerate() {
+        let y = height - 1 - row;
+
+        let mut columns = 0;
+
+        for (x, tile) in line.chars().enumerate() {
+            let column = x + 1;
+            columns += 1;
+
+            if x >= width {
+                return Err(error(line_number, column, format!("Row is longer than the first row ({} cells, line {})", width, first_line)))
+            }
+
+            match tile {
+                '.' => (),
+                '#' => obstacles.push((x, y)),
+                'A' => apples.push((x, y)),
+                'P' => portal_cells.push((x, y, line_number, column)),
+                'S' => {
+                    if start.is_some() {
+                        return Err(error(line_number, column, "Level has more than one snake start"))
+                    }
+                    start = Some((x, y));
+                }
+                tile => match Terrain::from_symbol(tile) {
+                    Some(kind) => terrain.push(((x, y), kind)),
+                    None => return Err(error(line_number, column, format!("Unknown tile `{}`", tile)))
+                }
+            }
+        }
+
+        if columns < width {
+            return Err(error(line_number, columns + 1, format!("Row is shorter than the first row ({} cells, line {})", width, first_line)))
+        }
+    }
+
+    if !portal_cells.len().is_multiple_of(2) {
+        let (_, _, line, column) = portal_cells[portal_cells.len() - 1];
+        return Err(error(line, column, "Portal has no partner"))
+    }
+
+    let Some(start) = start else {
+        return Err(error(first_line, 1, "Level has no snake start `S`"))
+    };
+
+    let portals: Vec<_> = portal_cells
+        .chunks(2)
+        .map(|pair| ((pair[0].0, pair[0].1), (pair[1].0, pair[1].1)))
+        .collect();
+
+    // The body trails behind the head the way `new_game` lays it out,
+    // and has to start on free cells.
+    let (step_x, step_y) = direction.opposite().offset();
+
+    for i in 1..SNAKE_STARTING_LEN as isize {
+        let x = start.0 as isize + step_x * i;
+        let y = start.1 as isize + step_y * i;
+
+        let inside = x >= 0 && y >= 0 && x < width as isize && y < height as isize;
+        if !inside && !wrap { break }
+
+        let cell = (x.rem_euclid(width as isize) as usize, y.rem_euclid(height as isize) as usize);
+
+        let blocked = obstacles.contains(&cell) || portals.iter().any(|&(one, two)| cell == one || cell == two);
+        if blocked {
+            let (line_number, _) = rows[height - 1 - cell.1];
+            return Err(error(line_number, cell.0 + 1, "The snake starts on a wall or portal here"))
+        }
+    }
+
+    Ok(Level {
+        name,
+        width,
+        height,
+        wrap,
+        speed,
+        speed_up,
+        fastest,
+        target,
+        apple_lifetime: lifetime,
+        fleeing,
+        start,
+        direction,
+        obstacles,
+        apples,
+        portals,
+        terrain
+    })
+}
@@ -1,9 +1,46 @@
+
 use std::rc::Rc;
 use std::cell::RefCell;
 
+mod campaign;
+mod canvas;
+mod editor;
+mod effects;
+mod rng;
+mod webgl;
+
+pub mod bot;
+pub mod env;
+pub mod game;
+pub mod generator;
+pub mod level;
+pub mod render;
+pub mod replay;
+pub mod net;
+pub mod particles;
+pub mod rollback;
+pub mod scene;
+pub mod spectate;
+pub mod text;
+pub mod tournament;
+
 use js_sys::Math::random;
 use wasm_bindgen::prelude::*;
-use web_sys::{WebGl2RenderingContext, WebGlProgram, WebGlShader};
+
+use bot::autopilot;
+use campaign::{Campaign, builtin_campaign, load_progress, save_progress};
+use canvas::Canvas2d;
+use editor::{Editor, Tile};
+use game::{Context, new_game, step, step_duration, survivor};
+use generator::{Layout, generate_level};
+use level::{Level, LevelError, Terrain, builtin_levels, parse_level};
+use net::Message;
+use particles::Particles;
+use render::{Effect, Renderer, ShaderError, letterbox};
+use rollback::Rollback;
+use scene::{Hud, editor_vertices, game_vertices, hud_vertices, particle_vertices};
+use spectate::{Broadcaster, Frame, Spectator};
+use webgl::WebGl;
 
 #[wasm_bindgen]
 extern "C" {
@@ -14,68 +51,208 @@ extern "C" {
     fn game_over(score: usize);
 
     #[wasm_bindgen(js_namespace = window)]
-    fn scored(score: usize);
+    fn clear_screen();
+
+    #[wasm_bindgen(js_namespace = window)]
+    fn apple_expired();
 
     #[wasm_bindgen(js_namespace = window)]
-    fn clear_screen();
+    fn level_complete(score: usize);
+
+    #[wasm_bindgen(js_namespace = window)]
+    fn level_started(name: &str, level: usize, levels: usize, target: Option<usize>);
+
+    #[wasm_bindgen(js_namespace = window)]
+    fn campaign_complete(score: usize);
+
+    #[wasm_bindgen(js_namespace = window)]
+    fn player_scored(player: usize, score: usize);
+
+    #[wasm_bindgen(js_namespace = window)]
+    fn versus_over(winner: Option<usize>);
 
     #[wasm_bindgen(js_namespace = window)]
-    fn pause();
+    fn net_send(message: &str);
+
+    #[wasm_bindgen(js_namespace = window)]
+    fn net_desync(tick: usize);
+
+    #[wasm_bindgen(js_namespace = window)]
+    fn net_rematch();
+
+    #[wasm_bindgen(js_namespace = window)]
+    fn broadcast(frame: &str);
+
+    #[wasm_bindgen(js_namespace = window)]
+    fn renderer_lost();
+
+    #[wasm_bindgen(js_namespace = window)]
+    fn renderer_restored();
 }
 
 const GRID_WIDTH: usize  = 16;
 const GRID_HEIGHT: usize = 10;
 
-const GRID_BOX_WIDTH: f32 = 1280. / GRID_WIDTH as f32;
-const GRID_BOX_HEIGHT: f32 = 800. / GRID_HEIGHT as f32;
+/// The size of the scene the board is built in. It's scaled to the screen when drawn.
+const SCENE_WIDTH: f32  = 1280.;
+const SCENE_HEIGHT: f32 = 800.;
+
+const GRID_BOX_WIDTH: f32 = SCENE_WIDTH / GRID_WIDTH as f32;
+const GRID_BOX_HEIGHT: f32 = SCENE_HEIGHT / GRID_HEIGHT as f32;
+
+pub(crate) const SNAKE_STARTING_LEN: usize = 4;
+
+pub const SNAKE_COLOUR: [f32; 3]         = [0.1, 0.65, 0.1];
+pub const SECOND_SNAKE_COLOUR: [f32; 3]  = [0.6, 0.25, 0.7];
+pub const APPLE_COLOUR: [f32; 3]         = [0.65, 0.1, 0.1];
+pub const FLEEING_APPLE_COLOUR: [f32; 3] = [0.85, 0.55, 0.1];
+pub const OBSTACLE_COLOUR: [f32; 3]      = [0.35, 0.3, 0.25];
+pub const PORTAL_COLOUR: [f32; 3]        = [0.3, 0.35, 0.8];
+pub const BACKGROUND_COLOUR: [f32; 3]    = [0.1, 0.2, 0.1];
+const EDITOR_GRID_COLOUR: [f32; 3]       = [0.13, 0.26, 0.13];
+const ICE_COLOUR: [f32; 3]               = [0.55, 0.75, 0.85];
+const MUD_COLOUR: [f32; 3]               = [0.3, 0.2, 0.1];
+const CONVEYOR_COLOUR: [f32; 3]          = [0.3, 0.3, 0.3];
+const GATE_COLOUR: [f32; 3]              = [0.5, 0.45, 0.2];
+
+/// Apple lifetime in ticks. Apples never expire if `None`.
+static mut APPLE_LIFETIME: Option<usize> = None;
+static mut FLEEING_APPLE_CHANCE: f64     = 0.;
+
+/// Queued turns of every player.
+static mut KEYS: [Vec<Direction>; 2]         = [vec![], vec![]];
+static mut QUEUED_ANIMATIONS: Vec<Animation> = vec![];
 
-static mut ANIMATION_DURATION: f64 = 220.;
+static mut LEVEL: Option<Level>   = None;
+static mut EDITOR: Option<Editor> = None;
 
-const STEP: f32 = GRID_BOX_WIDTH;
+static mut CAMPAIGN: Option<Campaign> = None;
 
-const SNAKE_STARTING_LEN: usize = 4;
+/// 1 for the classic game, 2 for two snakes on one keyboard.
+static mut PLAYERS: usize = 1;
 
-const SNAKE_COLOUR: [f32; 3] = [0.1, 0.65, 0.1];
-const APPLE_COLOUR: [f32; 3] = [0.65, 0.1, 0.1];
+/// Set while the first snake, or the one played online, is steered by the bot.
+static mut AUTOPILOT: bool = false;
 
-static mut KEYS: Vec<Direction>              = vec![];
-static mut QUEUED_ANIMATIONS: Vec<Animation> = vec![];
+/// The game against another browser, if one is being played.
+static mut ONLINE: Option<Rollback> = None;
+
+/// Set while the game played here is streamed to spectators.
+static mut BROADCASTER: Option<Broadcaster> = None;
+/// Set when watching someone else's game instead of playing.
+static mut SPECTATOR: Option<Spectator> = None;
+
+/// What the board ended up being drawn with, see `renderer_backend`.
+static mut BACKEND: &str = "";
+/// Post-processing drawn over the board, in the order it's applied.
+static mut EFFECTS: Vec<Effect> = vec![];
+/// What draws the board, once `start` has picked it.
+static mut RENDERER: Option<Rc<RefCell<Box<dyn Renderer>>>> = None;
+
+/// Set while the WebGL context is gone, to whether the game was being played when it went.
+static mut CONTEXT_LOST: Option<bool> = None;
+
+/// Sparks thrown off by eating, dying and speeding up.
+static mut PARTICLES: Particles = Particles::new(0x5EED);
+/// The board the last game ended on, drawn under the death explosion.
+static mut ENDED: Option<Context> = None;
+
+/// Seconds the current game has been played, pauses left out.
+static mut PLAYED: f64 = 0.;
 
 static mut PAUSED: bool    = true;
 static mut GAME_OVER: bool = false;
 
-struct Context
-{
-    window_height: f32,
-    window_width: f32,
-    snake: Vec<f32>,
-    apple: Option<(f32, f32)>,
-    direction: Direction
-}
-
 #[derive(PartialEq, Copy, Clone, Debug)]
-enum Direction {
+pub enum Direction {
     Up = 1,
     Down,
     Left,
     Right
 }
 
+impl Direction
+{
+    /// Grid offset of a single step in this direction. Up is towards higher rows.
+    pub fn offset(self) -> (isize, isize)
+    {
+        match self {
+            Direction::Up    => (0, 1),
+            Direction::Down  => (0, -1),
+            Direction::Left  => (-1, 0),
+            Direction::Right => (1, 0)
+        }
+    }
+
+    pub fn opposite(self) -> Direction
+    {
+        match self {
+            Direction::Up    => Direction::Down,
+            Direction::Down  => Direction::Up,
+            Direction::Left  => Direction::Right,
+            Direction::Right => Direction::Left
+        }
+    }
+
+    pub fn name(self) -> &'static str
+    {
+        match self {
+            Direction::Up    => "up",
+            Direction::Down  => "down",
+            Direction::Left  => "left",
+            Direction::Right => "right"
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Direction>
+    {
+        match name {
+            "up"    => Some(Direction::Up),
+            "down"  => Some(Direction::Down),
+            "left"  => Some(Direction::Left),
+            "right" => Some(Direction::Right),
+            _ => None
+        }
+    }
+}
+
 static mut CTX: Context = Context {
-    window_height: 0.0,
-    window_width: 0.0,
-    snake: vec![],
+    width: GRID_WIDTH,
+    height: GRID_HEIGHT,
+    snakes: vec![],
     apple: None,
-    direction: Direction::Left,
+    obstacles: vec![],
+    apple_spawns: vec![],
+    portals: vec![],
+    terrain: vec![],
+    wrap: true,
+    target: None,
+    apple_lifetime: None,
+    fleeing_chance: 0.,
+    tick_duration: 220.,
+    speed_up: level::SPEED_UP,
+    fastest: 0.,
+    ticks: 0,
+    rng: rng::Rng::new(0)
 };
 
+/// Borrows one of the statics above. They're only ever touched from the main
+/// thread, and the borrow mustn't outlive the call that made it.
+unsafe fn global<T>(global: *const T) -> &'static T
+{
+    &*global
+}
+
+unsafe fn global_mut<T>(global: *mut T) -> &'static mut T
+{
+    &mut *global
+}
+
 #[derive(Debug)]
 struct Animation
 {
     start_time: f64,
     duration: f64,
-    start_position: Vec<f32>,
-    end_position: Vec<f32>,
 
     is_paused: bool,
 
@@ -85,6 +262,23 @@ struct Animation
 
 impl Animation
 {
+    fn new(duration: f64) -> Animation
+    {
+        Animation {
+            start_time: now(),
+            duration,
+            is_paused: false,
+            pause_start_time: 0.,
+            pause_end_time: 0.
+        }
+    }
+
+    /// How far along the animation is, from 0 to 1.
+    fn progress(&self) -> f32
+    {
+        (self.elapsed() / self.duration).clamp(0., 1.) as f32
+    }
+
     fn done(&self) -> bool
     {
         self.elapsed() >= self.duration
@@ -113,831 +307,1025 @@ fn unpause_animation(animation: &mut Animation)
     animation.is_paused = false;
 }
 
-unsafe fn initiate_game(window_width: f32, window_height: f32)
+unsafe fn initiate_game()
 {
     PAUSED    = true;
     GAME_OVER = false;
+    PLAYED    = 0.;
 
-    QUEUED_ANIMATIONS.clear();
+    global_mut(&raw mut QUEUED_ANIMATIONS).clear();
 
-    CTX.snake = vec![];
+    let level = global_mut(&raw mut LEVEL).get_or_insert_with(|| builtin_levels().remove(0));
 
-    // Start off by going left.
-    KEYS.clear();
-    KEYS.push(Direction::Left);
-    CTX.direction = Direction::Left;
+    // Levels without their own food rules use the ones set from JS.
+    let mut rules = level.clone();
+    rules.apple_lifetime = level.apple_lifetime.or(APPLE_LIFETIME);
+    rules.fleeing        = if level.fleeing > 0. { level.fleeing } else { FLEEING_APPLE_CHANCE };
 
-    for i in 0..SNAKE_STARTING_LEN {
-        let mut part = create_box
-            (
-                (((window_width / 2.) / GRID_BOX_WIDTH).round() *  GRID_BOX_WIDTH) + (i as f32 * GRID_BOX_WIDTH),
-                ((window_height / 2.) / GRID_BOX_HEIGHT).round() * GRID_BOX_HEIGHT,
-                GRID_BOX_WIDTH,
-                GRID_BOX_HEIGHT,
-            );
+    CTX = new_game(&rules, PLAYERS, (random() * u32::MAX as f64) as u64);
 
-        CTX.snake.append(&mut part);
+    for (keys, snake) in global_mut(&raw mut KEYS).iter_mut().zip(&global(&raw const CTX).snakes) {
+        keys.clear();
+        keys.push(snake.direction);
     }
 
-    CTX.apple = Some(spawn_apple(&CTX));
+    match global(&raw const CAMPAIGN).as_ref() {
+        Some(campaign) => level_started(&level.name, campaign.current + 1, campaign.levels.len(), level.target),
+        None           => level_started(&level.name, 0, 0, level.target)
+    }
+
+    if let Some(broadcaster) = global_mut(&raw mut BROADCASTER).as_mut() {
+        broadcaster.reset();
+    }
+    broadcast_frame();
 }
 
-/// Stores the event into the global state that holds all
-/// queued events. This is used for the 'keypress' dom event.
+/// Sends the game as of now to the spectators, if there are any.
+unsafe fn broadcast_frame()
+{
+    let (Some(broadcaster), Some(level)) = (global_mut(&raw mut BROADCASTER).as_mut(), global(&raw const LEVEL).as_ref()) else { return };
+    broadcast(&broadcaster.frame(global(&raw const CTX), level).encode());
+}
+
+/// Starts streaming the game played here. Every tick a frame is passed
+/// to `window.broadcast`, to be sent on to the spectators.
 #[wasm_bindgen]
-pub unsafe fn key_press_event(event: web_sys::KeyboardEvent)
+pub fn broadcast_start()
 {
-    match event.key_code() {
-        // w
-        119 | 87 | 38 => {
-            if CTX.direction == Direction::Down { return }
-            if !KEYS.contains(&Direction::Up) && !KEYS.contains(&Direction::Down) {
-                KEYS.clear();
-                KEYS.push(Direction::Up)
-            }
-        }
+    unsafe {
+        BROADCASTER = Some(Broadcaster::default());
+        broadcast_frame();
+    }
+}
 
-        // s
-        115 | 83 | 40 => {
-            if CTX.direction == Direction::Up { return }
-            if !KEYS.contains(&Direction::Down) && !KEYS.contains(&Direction::Up) {
-                KEYS.clear();
-                KEYS.push(Direction::Down)
-            }
-        }
+#[wasm_bindgen]
+pub fn broadcast_stop()
+{
+    unsafe { BROADCASTER = None; }
+}
 
-        // d
-        100 | 68 | 39 => {
-            if CTX.direction == Direction::Left { return }
-            if !KEYS.contains(&Direction::Right) && !KEYS.contains(&Direction::Left) {
-                KEYS.clear();
-                KEYS.push(Direction::Right)
-            }
-        }
+/// Turns this page into a spectator. Nothing is played here anymore,
+/// and the keyboard is ignored. The game is only drawn from the
+/// frames passed to `spectator_receive`.
+#[wasm_bindgen]
+pub fn spectate()
+{
+    unsafe {
+        SPECTATOR = Some(Spectator::default());
+        EDITOR    = None;
+        ONLINE    = None;
+        PAUSED    = false;
+        global_mut(&raw mut QUEUED_ANIMATIONS).clear();
+    }
+}
 
-        // a
-        97 | 65 | 37 => {
-            if CTX.direction == Direction::Right { return }
-            if !KEYS.contains(&Direction::Left) && !KEYS.contains(&Direction::Right) {
-                KEYS.clear();
-                KEYS.push(Direction::Left)
-            }
-        }
+/// Takes in a frame of the watched game. Returns false if the frame
+/// could not be read, or has to wait for the next keyframe.
+#[wasm_bindgen]
+pub fn spectator_receive(frame: &str) -> bool
+{
+    let Some(frame) = Frame::decode(frame) else { return false };
 
-        32 => {
-            let previously_paused = PAUSED;
-            PAUSED = !PAUSED;
-            match previously_paused {
-                true => {
-                    clear_screen();
-                    for animation in QUEUED_ANIMATIONS.iter_mut() {
-                        unpause_animation(animation);
-                    }
-                }
-                false => {
-                    pause();
-                    for animation in QUEUED_ANIMATIONS.iter_mut() {
-                        pause_animation(animation);
-                    }
-                }
-            }
-        }
-        _ => ()
+    unsafe {
+        let Some(spectator) = global_mut(&raw mut SPECTATOR).as_mut() else { return false };
+        if !spectator.receive(&frame) { return false }
+
+        // Frames come in as the steps are taken, so each one is animated
+        // over the step duration, taking over from the previous one.
+        global_mut(&raw mut QUEUED_ANIMATIONS).clear();
+        global_mut(&raw mut QUEUED_ANIMATIONS).push(Animation::new(frame.tick_duration));
     }
+
+    true
 }
 
-#[wasm_bindgen(start)]
-fn start() -> Result<(), JsValue>
+/// Switches to one of the built-in levels and restarts the game.
+/// Returns the name of the level, or nothing if the index is out of range.
+#[wasm_bindgen]
+pub fn load_level(index: usize) -> Option<String>
 {
-    let window = web_sys::window().unwrap();
+    let level = builtin_levels().into_iter().nth(index)?;
+    let name  = level.name.clone();
 
-    let document = window.document().unwrap();
-    let canvas = document.get_element_by_id("canvas").unwrap();
-    let canvas: web_sys::HtmlCanvasElement = canvas.dyn_into::<web_sys::HtmlCanvasElement>()?;
+    unsafe {
+        CAMPAIGN = None;
+        LEVEL    = Some(level);
+        initiate_game();
+    }
 
-    let context = canvas
-        .get_context("webgl2")?
-        .unwrap()
-        .dyn_into::<WebGl2RenderingContext>()?;
+    Some(name)
+}
 
-    context.viewport(0, 0, 1280, 800);
+/// Parses a level in the text format and restarts the game on it.
+/// Returns the name of the level, or the line and column of the first parsing error.
+#[wasm_bindgen]
+pub fn load_level_from_string(source: &str) -> Result<String, LevelError>
+{
+    let level = parse_level(source)?;
+
+    if level.width != GRID_WIDTH || level.height != GRID_HEIGHT {
+        return Err(LevelError {
+            line: 1,
+            column: 1,
+            message: format!("Level must be {}x{} cells, got {}x{}", GRID_WIDTH, GRID_HEIGHT, level.width, level.height)
+        })
+    }
 
-    let vertex_shader = compile_shader
-    (
-        &context,
-        WebGl2RenderingContext::VERTEX_SHADER,
-        r##"#version 300 es
+    let name = level.name.clone();
 
-        uniform vec2 resolution;
+    unsafe {
+        CAMPAIGN = None;
+        LEVEL    = Some(level);
+        initiate_game();
+    }
 
-        in vec2 position;
+    Ok(name)
+}
 
-        in vec3 vertexColour;
-        out vec3 fragmentColour;
+/// Generates a level with one of the `maze`, `rooms`, `pillars` or `arena`
+/// layouts and restarts the game on it. Returns the name of the level.
+#[wasm_bindgen]
+pub fn load_generated_level(layout: &str, seed: u32) -> Option<String>
+{
+    let level = generate_level(Layout::from_name(layout)?, GRID_WIDTH, GRID_HEIGHT, seed as u64);
+    let name  = level.name.clone();
 
-        void main() {
-            fragmentColour = vertexColour;
+    unsafe {
+        CAMPAIGN = None;
+        LEVEL    = Some(level);
+        initiate_game();
+    }
 
-            vec2 zeroToOne = position / resolution;
-            vec2 zeroToTwo = zeroToOne * 2.0;
-            vec2 clipSpace = zeroToTwo - 1.0;
-            gl_Position = vec4(clipSpace, 0, 1);
-        }
-        "##,
-    )?;
+    Some(name)
+}
+
+/// Starts the campaign from the first level not yet completed,
+/// or from the very beginning if `restart` is set.
+/// Returns false if every level has been completed already.
+#[wasm_bindgen]
+pub fn start_campaign(restart: bool) -> bool
+{
+    let progress = if restart { 0 } else { load_progress() };
+    let Some(campaign) = Campaign::new(builtin_campaign(), progress) else { return false };
 
-    let fragment_shader = compile_shader
-    (
-        &context,
-        WebGl2RenderingContext::FRAGMENT_SHADER,
-        r##"#version 300 es
+    unsafe {
+        LEVEL    = Some(campaign.level().clone());
+        CAMPAIGN = Some(campaign);
+        initiate_game();
+    }
 
-        precision highp float;
+    true
+}
 
-        in vec3 fragmentColour;
-        out vec4 outColour;
+/// Called once the target of the current level is reached.
+/// In the campaign this moves on to the next level.
+unsafe fn complete_level(score: usize)
+{
+    let Some(campaign) = global_mut(&raw mut CAMPAIGN).as_mut() else {
+        initiate_game();
+        return level_complete(score)
+    };
 
-        void main() {
-            outColour = vec4(fragmentColour, 1.0);
-        }
-        "##
-    )?;
+    save_progress(campaign.current + 1);
 
-    let program = link_program(&context, &vertex_shader, &fragment_shader)?;
-    context.use_program(Some(&program));
+    // The next level is started first, so JS already knows about it when told.
+    match campaign.advance() {
+        true => {
+            LEVEL = Some(campaign.level().clone());
+            initiate_game();
+            level_complete(score);
+        }
+        false => {
+            CAMPAIGN = None;
+            LEVEL    = None;
+            initiate_game();
+            campaign_complete(score);
+        }
+    }
+}
 
-    let window_width = canvas.width() as f32;
-    let window_height = canvas.height() as f32;
+/// Switches between the classic game and two players on one keyboard,
+/// then restarts the game on the current level.
+///
+/// With two players WASD steers the first snake and the arrow keys
+/// the second one. The last snake alive wins, as does the first one
+/// to reach the target of the level.
+#[wasm_bindgen]
+pub fn set_players(players: usize)
+{
+    unsafe {
+        PLAYERS  = players.clamp(1, 2);
+        CAMPAIGN = None;
+        initiate_game();
+    }
+}
 
-    let resolution_location = context.get_uniform_location(&program, "resolution").unwrap();
-    context.uniform2f(Some(&resolution_location), window_width, window_height);
+/// Lets the bot steer instead of the keyboard. With two players
+/// on one keyboard it takes over the first snake.
+#[wasm_bindgen]
+pub fn set_autopilot(enabled: bool)
+{
+    unsafe { AUTOPILOT = enabled; }
+}
 
-    let f = Rc::new(RefCell::new(None));
-    let g = f.clone();
+/// How the board is drawn: `webgl2`, or `canvas2d` where WebGL 2 isn't available.
+#[wasm_bindgen]
+pub fn renderer_backend() -> String
+{
+    unsafe { String::from(BACKEND) }
+}
 
-    let mut resulting_position = Vec::with_capacity(2000);
-    let mut colours            = Vec::with_capacity(4000);
+/// Turns an effect drawn over the board on or off: `crt`, `bloom`, `vignette`
+/// or `chromatic-aberration`. Gives false for anything else. Only WebGL draws them.
+#[wasm_bindgen]
+pub fn set_effect(name: &str, enabled: bool) -> bool
+{
+    let Some(effect) = Effect::from_name(name) else { return false };
 
     unsafe {
-        CTX = Context {
-            window_width,
-            window_height,
-            snake: vec![],
-            apple: None,
-            direction: Direction::Left
-        };
+        global_mut(&raw mut EFFECTS).retain(|&e| e != effect);
+        if enabled { global_mut(&raw mut EFFECTS).push(effect); }
+        global_mut(&raw mut EFFECTS).sort();
+    }
 
-        initiate_game(window_width, window_height);
+    true
+}
 
-        *g.borrow_mut() = Some(Closure::new(move || {
-            if PAUSED {
-                request_animation_frame(f.borrow().as_ref().unwrap());
-                return
-            }
+/// Draws the board with other shaders, for skins. They need the `position` and
+/// `vertexColour` attributes and the `resolution` uniform of the built-in ones,
+/// and can have a `time` uniform, in seconds. If they don't work the built-in
+/// ones are put back, and the error says what went wrong and where.
+#[wasm_bindgen]
+pub fn load_shaders(vertex: &str, fragment: &str) -> Result<(), ShaderError>
+{
+    let renderer = unsafe { global(&raw const RENDERER).as_ref() }
+        .ok_or_else(|| ShaderError::new("renderer", "Nothing is drawn yet"))?;
 
-            for active_key in &KEYS {
-                handle_key_action(&mut CTX, &mut QUEUED_ANIMATIONS, *active_key);
-            }
+    renderer.borrow_mut().load_shaders(vertex, fragment)
+}
 
-            QUEUED_ANIMATIONS.retain(|a| {
-                let done = a.done();
-
-                // Finish off the animation movement if it has ended.
-                // This is to avoid misalignment of the snake end position
-                // if the frame rate does not match the animation end time.
-                if done {
-                    CTX.snake[0..12].copy_from_slice(&a.end_position[0..12]);
-                    block_exceeds_screen_edge(&CTX, &mut CTX.snake[0..12], &mut resulting_position);
-
-                    let snake_len = CTX.snake.len();
-                    block_exceeds_screen_edge
-                    (
-                        &CTX,
-                        &mut CTX.snake[snake_len - 12..snake_len],
-                        &mut resulting_position
-                    );
-
-                    // Fill out the preceding block of the tail.
-                    // This avoids having a gap in front of the tail block
-                    // when crossing the screen boundary.
-                    {
-                        let snake_len = CTX.snake.len();
-
-                        let x = CTX.snake[snake_len - 12];
-                        let y = CTX.snake[snake_len - 11];
-
-                        if x < 0. {
-                            let mut vertices = create_box(CTX.window_width - GRID_BOX_WIDTH, y, GRID_BOX_WIDTH, GRID_BOX_HEIGHT);
-                            resulting_position.append(&mut vertices);
-                        }
+/// Takes in a message from the relay. `join` starts a new online game,
+/// everything else is passed on to the one being played.
+/// Returns false if the message could not be read.
+#[wasm_bindgen]
+pub fn net_receive(message: &str) -> bool
+{
+    let Some(message) = Message::decode(message) else { return false };
 
-                        if x + GRID_BOX_WIDTH > CTX.window_width {
-                            let mut vertices = create_box(0., y, GRID_BOX_WIDTH, GRID_BOX_HEIGHT);
-                            resulting_position.append(&mut vertices);
-                        }
+    unsafe {
+        match (message, global_mut(&raw mut ONLINE).as_mut()) {
+            (Message::Join { player, seed, level }, _) => {
+                let level  = builtin_levels().into_iter().nth(level).unwrap_or_else(|| builtin_levels().remove(0));
+                let online = Rollback::new(player.min(1), &level, seed);
+
+                for (keys, snake) in global_mut(&raw mut KEYS).iter_mut().zip(&online.ctx.snakes) {
+                    keys.clear();
+                    keys.push(snake.direction);
+                }
 
-                        if y < 0. {
-                            let mut vertices = create_box(x, CTX.window_height - GRID_BOX_HEIGHT, GRID_BOX_WIDTH, GRID_BOX_HEIGHT);
-                            resulting_position.append(&mut vertices);
-                        }
+                global_mut(&raw mut QUEUED_ANIMATIONS).clear();
+                CAMPAIGN  = None;
+                ONLINE    = Some(online);
+                GAME_OVER = false;
+                PLAYED    = 0.;
 
-                        if y + GRID_BOX_HEIGHT > CTX.window_height {
-                            let mut vertices = create_box(x, 0., GRID_BOX_WIDTH, GRID_BOX_HEIGHT);
-                            resulting_position.append(&mut vertices);
-                        }
-                    }
+                // Without a renderer the game starts once there's one again.
+                match global_mut(&raw mut CONTEXT_LOST).as_mut() {
+                    Some(playing) => *playing = true,
+                    None          => PAUSED = false
                 }
-
-                !done
-            });
-
-            if did_the_snek_eat_the_apple(&CTX) {
-                let snake_len = CTX.snake.len();
-                CTX.snake.append
-                (
-                    &mut create_box
-                    (
-                        CTX.snake[snake_len - 12],
-                        CTX.snake[snake_len - 11],
-                        GRID_BOX_WIDTH,
-                        GRID_BOX_HEIGHT
-                    )
-                );
-                CTX.apple = Some(spawn_apple(&CTX));
-                scored(CTX.snake.len() / 12 - SNAKE_STARTING_LEN);
-
-                ANIMATION_DURATION *= 0.975;
             }
+            (message, Some(online)) => {
+                let desynced = online.desync().is_some();
 
-            if collisions(&CTX) {
-                GAME_OVER = true;
-                game_over(CTX.snake.len() / 12 - SNAKE_STARTING_LEN);
-                initiate_game(window_width, window_height);
-            }
+                // A wrong guess can change who ate what.
+                if online.receive(message) {
+                    for (player, snake) in online.ctx.snakes.iter().enumerate() {
+                        player_scored(player, snake.score);
+                    }
+                }
 
-            snake_movement(&mut CTX, &QUEUED_ANIMATIONS, &mut resulting_position);
+                send_messages(online);
+                report_desync(online, desynced);
+            }
+            (_, None) => ()
+        }
+    }
 
-            colours.append(&mut SNAKE_COLOUR.repeat(resulting_position.len() / 2));
+    true
+}
 
-            if let Some(apple) = CTX.apple {
-                let mut apple_vertices = create_box(apple.0, apple.1, GRID_BOX_WIDTH, GRID_BOX_HEIGHT);
+/// Leaves the online game and goes back to playing alone.
+#[wasm_bindgen]
+pub fn net_leave()
+{
+    unsafe {
+        ONLINE = None;
+        initiate_game();
+    }
+}
 
-                colours.append(&mut APPLE_COLOUR.repeat(apple_vertices.len() / 2));
-                resulting_position.append(&mut apple_vertices);
-            }
+/// Ends a two player game. Without a winner it's a draw.
+unsafe fn end_versus(winner: Option<usize>)
+{
+    GAME_OVER = true;
+    versus_over(winner);
+    initiate_game();
+}
 
-            let vertices_count = resulting_position.len() / 2;
-            let vertices_count= vertices_count as i32;
+/// Sets how many ticks an apple stays on the board before expiring.
+/// Passing 0 makes apples last forever. Levels with their own lifetime keep it.
+#[wasm_bindgen]
+pub fn set_apple_lifetime(ticks: usize)
+{
+    unsafe {
+        APPLE_LIFETIME = match ticks {
+            0 => None,
+            _ => Some(ticks)
+        };
 
-            // Background colour.
-            context.clear_color(0.1, 0.2, 0.1, 1.0);
-            context.clear(WebGl2RenderingContext::COLOR_BUFFER_BIT);
+        let level = global_mut(&raw mut LEVEL).get_or_insert_with(|| builtin_levels().remove(0));
+        CTX.apple_lifetime = level.apple_lifetime.or(APPLE_LIFETIME);
+    }
+}
 
-            draw_vertices
-            (
-                &context,
-                &program,
-                resulting_position.drain(..).as_slice(),
-                colours.drain(..).as_slice()
-            ).expect("Drawing failed");
+/// Sets the chance (0 to 1) that a newly spawned apple runs away from the snake.
+/// Levels with their own chance keep it.
+#[wasm_bindgen]
+pub fn set_fleeing_apple_chance(chance: f64)
+{
+    unsafe {
+        FLEEING_APPLE_CHANCE = chance.clamp(0., 1.);
 
-            context.draw_arrays(WebGl2RenderingContext::TRIANGLES, 0, vertices_count);
+        let level = global_mut(&raw mut LEVEL).get_or_insert_with(|| builtin_levels().remove(0));
+        CTX.fleeing_chance = if level.fleeing > 0. { level.fleeing } else { FLEEING_APPLE_CHANCE };
+    }
+}
 
-            request_animation_frame(f.borrow().as_ref().unwrap());
-        }));
+/// Opens the level editor on the current level. The game is paused while editing.
+#[wasm_bindgen]
+pub fn editor_open()
+{
+    unsafe {
+        let level = global_mut(&raw mut LEVEL).get_or_insert_with(|| builtin_levels().remove(0));
+        EDITOR = Some(Editor::from_level(level));
+        PAUSED = true;
+    }
+}
 
-        request_animation_frame(g.borrow().as_ref().unwrap());
+/// Closes the editor and starts the game on the edited level.
+/// A broken level keeps the editor open.
+#[wasm_bindgen]
+pub fn editor_close() -> Result<(), LevelError>
+{
+    unsafe {
+        let Some(editor) = global(&raw const EDITOR).as_ref() else { return Ok(()) };
+        LEVEL  = Some(parse_level(&editor.export())?);
+        EDITOR = None;
+        initiate_game();
     }
 
     Ok(())
 }
 
-fn window() -> web_sys::Window
+/// Picks what the mouse paints: `wall`, `portal`, `start`, `apple`, `erase`,
+/// `ice`, `mud`, or a `conveyor-` or `gate-` followed by `up`, `down`, `left` or `right`.
+#[wasm_bindgen]
+pub fn editor_set_brush(brush: &str) -> bool
 {
-    web_sys::window().expect("no global `window` exists")
+    let brush = match brush.split_once('-') {
+        Some(("conveyor", name)) => match Direction::from_name(name) {
+            Some(direction) => Tile::Terrain(Terrain::Conveyor(direction)),
+            None => return false
+        },
+        Some(("gate", name)) => match Direction::from_name(name) {
+            Some(direction) => Tile::Terrain(Terrain::Gate(direction)),
+            None => return false
+        },
+        _ => match brush {
+            "wall"   => Tile::Wall,
+            "portal" => Tile::Portal,
+            "start"  => Tile::Start,
+            "apple"  => Tile::Apple,
+            "erase"  => Tile::Empty,
+            "ice"    => Tile::Terrain(Terrain::Ice),
+            "mud"    => Tile::Terrain(Terrain::Mud),
+            _ => return false
+        }
+    };
+
+    unsafe {
+        let Some(editor) = global_mut(&raw mut EDITOR).as_mut() else { return false };
+        editor.brush = brush;
+    }
+
+    true
 }
 
-fn request_animation_frame(f: &Closure<dyn FnMut()>)
+/// Returns the name of the edited level.
+#[wasm_bindgen]
+pub fn editor_name() -> Option<String>
 {
-    window()
-        .request_animation_frame(f.as_ref().unchecked_ref())
-        .expect("should register `requestAnimationFrame` OK");
+    unsafe { global(&raw const EDITOR).as_ref().map(|editor| editor.name.clone()) }
 }
 
-fn spawn_apple(ctx: &Context) -> (f32, f32)
+/// Sets the header rules of the edited level. A target of 0 means no target.
+#[wasm_bindgen]
+pub fn editor_set_rules(name: &str, wrap: bool, speed: f64, target: usize)
 {
-    let vertical_blocks   = GRID_WIDTH / GRID_BOX_WIDTH as usize;
-    let horizontal_blocks = GRID_HEIGHT / GRID_BOX_HEIGHT as usize;
+    unsafe {
+        let Some(editor) = global_mut(&raw mut EDITOR).as_mut() else { return };
+        editor.name   = name.to_string();
+        editor.wrap   = wrap;
+        editor.speed  = speed.max(1.);
+        editor.target = match target {
+            0 => None,
+            _ => Some(target)
+        };
+    }
+}
 
-    let mut unoccupied: Vec<(f32, f32)> = Vec::with_capacity(vertical_blocks * horizontal_blocks);
+#[wasm_bindgen]
+pub fn editor_mouse_down(event: web_sys::MouseEvent)
+{
+    unsafe {
+        let Some(editor) = global_mut(&raw mut EDITOR).as_mut().filter(|e| !e.playtesting) else { return };
+        let Some((x, y)) = mouse_cell(&event) else { return };
 
-    for i in (0..GRID_WIDTH * GRID_BOX_WIDTH as usize).step_by(GRID_BOX_WIDTH as usize) {
-        for j in (0..GRID_HEIGHT * GRID_BOX_HEIGHT as usize).step_by(GRID_BOX_HEIGHT as usize) {
-            let mut occupied = false;
+        editor.begin_stroke();
+        editor.paint(x, y, true);
+    }
+}
 
-            for k in (0..ctx.snake.len()).step_by(12) {
-                let snake = create_box(ctx.snake[k], ctx.snake[k + 1], GRID_BOX_WIDTH, GRID_BOX_HEIGHT);
-                let block = create_box(i as f32, j as f32, GRID_BOX_WIDTH, GRID_BOX_HEIGHT);
-                if !box_collision(&snake, &block) { continue }
+#[wasm_bindgen]
+pub fn editor_mouse_move(event: web_sys::MouseEvent)
+{
+    unsafe {
+        let Some(editor) = global_mut(&raw mut EDITOR).as_mut().filter(|e| !e.playtesting) else { return };
 
-                occupied = true;
-                break;
-            }
+        editor.hover = mouse_cell(&event);
 
-            if !occupied { unoccupied.push((i as f32, j as f32)); }
+        if let (true, Some((x, y))) = (editor.painting, editor.hover) {
+            editor.paint(x, y, false);
         }
     }
+}
 
-    let seed     = random() * 2000.;
-    let seed     = seed as usize;
-    let position = seed % unoccupied.len();
-
-    (unoccupied[position].0, unoccupied[position].1)
+#[wasm_bindgen]
+pub fn editor_mouse_up()
+{
+    unsafe {
+        if let Some(editor) = global_mut(&raw mut EDITOR).as_mut() { editor.end_stroke(); }
+    }
 }
 
-fn snake_movement(ctx: &mut Context, animations: &[Animation], resulting_position: &mut Vec<f32>)
+#[wasm_bindgen]
+pub fn editor_undo()
 {
-    let mut end_position = ctx.snake.clone();
+    unsafe {
+        if let Some(editor) = global_mut(&raw mut EDITOR).as_mut() { editor.undo(); }
+    }
+}
 
-    for animation in animations {
-        if animation.done() { continue }
+#[wasm_bindgen]
+pub fn editor_redo()
+{
+    unsafe {
+        if let Some(editor) = global_mut(&raw mut EDITOR).as_mut() { editor.redo(); }
+    }
+}
 
-        let interpolation_factor = (animation.elapsed() / animation.duration) as f32;
-        let animation_len = animation.start_position.len();
+/// Returns the edited level in the text format.
+#[wasm_bindgen]
+pub fn editor_export() -> Option<String>
+{
+    unsafe { global(&raw const EDITOR).as_ref().map(|editor| editor.export()) }
+}
 
-        // Lerp the head.
-        for i in 0..12 {
-            let delta = animation.end_position[i] - animation.start_position[i];
-            end_position[i] = animation.start_position[i] + delta * interpolation_factor;
-        }
+/// Starts or stops playing the edited level without leaving the editor.
+#[wasm_bindgen]
+pub fn editor_playtest(enabled: bool) -> Result<(), LevelError>
+{
+    unsafe {
+        let Some(editor) = global_mut(&raw mut EDITOR).as_mut() else { return Ok(()) };
 
-        for i in 12..animation_len {
-            // Fills the background of the snake with snake body tiles.
-            // This is so "turns" are smoother - they are filled with a snake tile
-            // underneath so the corners aren't "smoothed" while turning.
-            // Head is excluded so the head movement animation remains smooth.
-            // Otherwise the "below" tile would just appear at the end position.
-            resulting_position.push(animation.end_position[i]);
-            end_position[i] = animation.end_position[i];
+        if enabled {
+            LEVEL = Some(parse_level(&editor.export())?);
+            initiate_game();
         }
 
-        // Also lerp the tail.
-        for i in animation_len - 12..animation_len {
-            let delta = animation.end_position[i] - animation.start_position[i];
-            end_position[i] = ((animation.start_position[i] + delta * interpolation_factor) / 10.).round() * 10.;
-        }
+        editor.playtesting = enabled;
+        PAUSED = true;
     }
 
-    // Fill out the preceding block of the tail.
-    // This avoids having a gap in front of the tail block
-    // when crossing the screen boundary.
-    {
-        let snake_len = ctx.snake.len();
+    Ok(())
+}
 
-        let x = ctx.snake[snake_len - 12];
-        let y = ctx.snake[snake_len - 11];
+/// Converts the mouse position into the grid cell under it.
+fn mouse_cell(event: &web_sys::MouseEvent) -> Option<(usize, usize)>
+{
+    let canvas = event.target()?.dyn_into::<web_sys::HtmlCanvasElement>().ok()?;
 
-        if x < 0. {
-            let mut vertices = create_box(ctx.window_width - GRID_BOX_WIDTH, y, GRID_BOX_WIDTH, GRID_BOX_HEIGHT);
-            resulting_position.append(&mut vertices);
-        }
+    // The scene only takes up part of the canvas, see `letterbox`.
+    let (client_width, client_height) = (canvas.client_width() as f64, canvas.client_height() as f64);
+    let (left, bottom, width, height) = letterbox(client_width, client_height);
 
-        if x + GRID_BOX_WIDTH > ctx.window_width {
-            let mut vertices = create_box(0., y, GRID_BOX_WIDTH, GRID_BOX_HEIGHT);
-            resulting_position.append(&mut vertices);
-        }
+    let x = (event.offset_x() as f64 - left) / width * SCENE_WIDTH as f64;
+    let y = (client_height - event.offset_y() as f64 - bottom) / height * SCENE_HEIGHT as f64;
 
-        if y < 0. {
-            let mut vertices = create_box(x, ctx.window_height - GRID_BOX_HEIGHT, GRID_BOX_WIDTH, GRID_BOX_HEIGHT);
-            resulting_position.append(&mut vertices);
-        }
+    if x < 0. || y < 0. { return None }
 
-        if y + GRID_BOX_HEIGHT > ctx.window_height {
-            let mut vertices = create_box(x, 0., GRID_BOX_WIDTH, GRID_BOX_HEIGHT);
-            resulting_position.append(&mut vertices);
+    let x = (x / GRID_BOX_WIDTH as f64) as usize;
+    let y = (y / GRID_BOX_HEIGHT as f64) as usize;
+
+    (x < GRID_WIDTH && y < GRID_HEIGHT).then_some((x, y))
+}
+
+/// Stores the event into the global state that holds all
+/// queued events. This is used for the 'keypress' dom event.
+///
+/// # Safety
+/// Touches the global game state, so it must only be called from the main thread.
+#[wasm_bindgen]
+pub unsafe fn key_press_event(event: web_sys::KeyboardEvent)
+{
+    if global(&raw const SPECTATOR).is_some() { return }
+
+    if let Some(editor) = global_mut(&raw mut EDITOR).as_mut() {
+        match (editor.playtesting, event.key_code()) {
+            // Escape stops the playtest.
+            (true, 27) => {
+                editor.playtesting = false;
+                PAUSED = true;
+                clear_screen();
+                return
+            }
+            (true, _) => (),
+
+            // z and y
+            (false, 90) if event.ctrl_key() && event.shift_key() => return editor.redo(),
+            (false, 90) if event.ctrl_key() => return editor.undo(),
+            (false, 89) if event.ctrl_key() => return editor.redo(),
+            (false, _) => return
         }
     }
 
-    resulting_position.append(&mut ctx.snake.clone());
+    // With a single player both WASD and the arrows steer the same snake.
+    let arrows = PLAYERS - 1;
+
+    let (player, direction) = match event.key_code() {
+        // w, s, d, a
+        119 | 87  => (0, Direction::Up),
+        115 | 83  => (0, Direction::Down),
+        100 | 68  => (0, Direction::Right),
+        97 | 65   => (0, Direction::Left),
+
+        38 => (arrows, Direction::Up),
+        40 => (arrows, Direction::Down),
+        39 => (arrows, Direction::Right),
+        37 => (arrows, Direction::Left),
+
+        // The other player can't be paused. Once the game is over space asks for a rematch.
+        32 if global(&raw const ONLINE).is_some() => {
+            if GAME_OVER {
+                net_send(&Message::Rematch.encode());
+                net_rematch();
+            }
+            return
+        }
+        // The game can't go on until there's something to draw it with again.
+        32 if global(&raw const CONTEXT_LOST).is_some() => return,
+        32 => {
+            let previously_paused = PAUSED;
+            PAUSED = !PAUSED;
+            match previously_paused {
+                true => {
+                    clear_screen();
+                    for animation in global_mut(&raw mut QUEUED_ANIMATIONS).iter_mut() {
+                        unpause_animation(animation);
+                    }
+                }
+                false => {
+                    for animation in global_mut(&raw mut QUEUED_ANIMATIONS).iter_mut() {
+                        pause_animation(animation);
+                    }
+                }
+            }
+            return
+        }
+        _ => return
+    };
 
-    for i in (0..end_position.len()).step_by(12) {
-        block_exceeds_screen_edge(ctx, &mut end_position[i..i + 12], resulting_position);
-    }
+    // Online, every key steers this side's snake.
+    let (player, ctx) = match global(&raw const ONLINE).as_ref() {
+        Some(online) => (online.player, &online.ctx),
+        None         => (player, global(&raw const CTX))
+    };
 
-    ctx.snake = end_position;
+    let Some(snake) = ctx.snakes.get(player) else { return };
+    if snake.direction == direction.opposite() { return }
+
+    // Only a single turn is queued at a time, and it can't be undone by the opposite key.
+    let keys = &mut KEYS[player];
+    if !keys.contains(&direction) && !keys.contains(&direction.opposite()) {
+        keys.clear();
+        keys.push(direction)
+    }
 }
 
-fn block_exceeds_screen_edge
-(
-    ctx: &Context,
-    block: &mut [f32],
-    resulting_position: &mut Vec<f32>
-)
+#[wasm_bindgen(start)]
+fn start() -> Result<(), JsValue>
 {
-    let x = block[0];
-    let y = block[1];
+    let window = web_sys::window().unwrap();
 
-    // Right
-    if x >= ctx.window_width {
-        block.copy_from_slice(&create_box(0., y, GRID_BOX_WIDTH, GRID_BOX_HEIGHT));
-    }
+    let document = window.document().unwrap();
+    let canvas = document.get_element_by_id("canvas").unwrap();
+    let canvas: web_sys::HtmlCanvasElement = canvas.dyn_into::<web_sys::HtmlCanvasElement>()?;
 
-    if x + GRID_BOX_WIDTH > ctx.window_width {
-        let width = (x + GRID_BOX_WIDTH) - ctx.window_width;
-        let width = width.min(GRID_BOX_WIDTH);
+    let (canvas, renderer) = create_renderer(canvas)?;
+    let renderer = Rc::new(RefCell::new(renderer));
 
-        let mut vertices = create_box(0., y, width, GRID_BOX_HEIGHT);
-        resulting_position.append(&mut vertices);
+    unsafe {
+        BACKEND  = renderer.borrow().backend();
+        RENDERER = Some(renderer.clone());
     }
 
-    // Left
-    if x + GRID_BOX_WIDTH <= 0. {
-        block.copy_from_slice
-        (
-            &create_box(ctx.window_width - GRID_BOX_WIDTH, y, GRID_BOX_WIDTH, GRID_BOX_HEIGHT)
-        );
-    }
+    watch_context(&canvas, renderer.clone())?;
 
-    if x <= 0. {
-        let hidden_width = 0. - x;
-        let mut vertices = create_box(ctx.window_width - hidden_width, y, hidden_width, GRID_BOX_HEIGHT);
-        resulting_position.append(&mut vertices);
-    }
+    fit_canvas(&canvas, renderer.borrow_mut().as_mut());
 
-    // Up
-    if y >= ctx.window_height {
-        block.copy_from_slice(&create_box(x, 0., GRID_BOX_WIDTH, GRID_BOX_HEIGHT));
-    }
+    // Resizing the window and zooming in or out both change how many pixels the canvas takes up.
+    // The canvas is looked up every time, it's swapped for a fresh one if WebGL stops working.
+    let resized = {
+        let renderer = renderer.clone();
+        Closure::<dyn FnMut()>::new(move || {
+            let canvas = document.get_element_by_id("canvas").and_then(|canvas| canvas.dyn_into().ok());
+            if let Some(canvas) = canvas {
+                fit_canvas(&canvas, renderer.borrow_mut().as_mut());
+            }
+        })
+    };
+    window.add_event_listener_with_callback("resize", resized.as_ref().unchecked_ref())?;
+    resized.forget();
 
-    if y + GRID_BOX_HEIGHT >= ctx.window_height {
-        let height = y - ctx.window_height;
-        let mut vertices = create_box(x, height, GRID_BOX_WIDTH, GRID_BOX_HEIGHT);
-        resulting_position.append(&mut vertices);
-    }
+    let f = Rc::new(RefCell::new(None));
+    let g = f.clone();
 
-    // Down
-    if y + GRID_BOX_HEIGHT <= 0. {
-        block.copy_from_slice
-        (
-            &create_box(x, ctx.window_height - GRID_BOX_HEIGHT, GRID_BOX_WIDTH, GRID_BOX_HEIGHT)
-        );
-    }
+    let mut resulting_position = Vec::with_capacity(2000);
+    let mut colours            = Vec::with_capacity(4000);
+    let mut last_frame         = now();
+    // How far the step animation was, kept for the frames drawn while paused.
+    let mut progress           = 1.;
 
-    if y <= 0. {
-        let height = y.abs();
-        let mut vertices = create_box(x, ctx.window_height - height, GRID_BOX_WIDTH, GRID_BOX_HEIGHT);
-        resulting_position.append(&mut vertices);
-    }
-}
+    unsafe {
+        initiate_game();
 
-// AABB vs AABB
-// https://developer.mozilla.org/en-US/docs/Games/Techniques/3D_collision_detection#aabb_vs._aabb
-fn box_collision(one: &[f32], two: &[f32]) -> bool
-{
-    // a.minX <= b.maxX &&
-    // a.maxX >= b.minX &&
-    // a.minY <= b.maxY &&
-    // a.maxY >= b.minY &&
+        *g.borrow_mut() = Some(Closure::new(move || {
+            // Paused frames use up their time too, so particles don't jump ahead when the game goes on.
+            let seconds = ((now() - last_frame) / 1000.) as f32;
+            last_frame  = now();
 
-    let collision_x = one[0] < two[0] + GRID_BOX_WIDTH - 15. && one[0] + GRID_BOX_WIDTH - 15. > two[0];
-    let collision_y = one[1] < two[1] + GRID_BOX_HEIGHT - 15. && one[1] + GRID_BOX_HEIGHT - 15. > two[1];
-    collision_x && collision_y
-}
+            if let Some(editor) = global(&raw const EDITOR).as_ref().filter(|e| !e.playtesting) {
+                editor_vertices(editor, &mut resulting_position, &mut colours);
+                render(renderer.borrow_mut().as_mut(), &mut resulting_position, &mut colours);
 
-/// Returns a boolean saying whether the snake head hid the body.
-fn collisions(ctx: &Context) -> bool
-{
-    let head = &ctx.snake[0..12];
-    for i in (36..ctx.snake.len()).step_by(12) {
-        if box_collision(head, &ctx.snake[i..i + 12]) { return true }
+                request_animation_frame(f.borrow().as_ref().unwrap());
+                return
+            }
+
+            if let Some(spectator) = global(&raw const SPECTATOR).as_ref() {
+                let progress = global(&raw const QUEUED_ANIMATIONS).first().map(|a| a.progress()).unwrap_or(1.);
+
+                if let Some(ctx) = spectator.ctx.as_ref() {
+                    game_vertices(ctx, progress, &mut resulting_position, &mut colours);
+                    hud_vertices(ctx, &Hud::default(), &mut resulting_position, &mut colours);
+                }
+                render(renderer.borrow_mut().as_mut(), &mut resulting_position, &mut colours);
+
+                request_animation_frame(f.borrow().as_ref().unwrap());
+                return
+            }
+
+            match PAUSED {
+                true => {
+                    // The explosion of a game that just ended plays out, anything else waits for the game.
+                    if global(&raw const ENDED).is_some() {
+                        global_mut(&raw mut PARTICLES).update(seconds);
+                    }
+
+                    // A new game starts out with nothing moving.
+                    if global(&raw const QUEUED_ANIMATIONS).is_empty() {
+                        progress = 1.;
+                    }
+                }
+                false => {
+                    ENDED   = None;
+                    PLAYED += seconds as f64;
+                    global_mut(&raw mut PARTICLES).update(seconds);
+
+                    global_mut(&raw mut QUEUED_ANIMATIONS).retain(|a| !a.done());
+
+                    // The game moves a whole cell at a time. The animation
+                    // of that step is played until the next one is due.
+                    if global(&raw const QUEUED_ANIMATIONS).is_empty() {
+                        match global_mut(&raw mut ONLINE).as_mut() {
+                            Some(online) => online_step(online),
+                            None         => local_step()
+                        }
+                    }
+
+                    progress = global(&raw const QUEUED_ANIMATIONS).first().map(|a| a.progress()).unwrap_or(1.);
+                }
+            }
+
+            let ctx = match global(&raw const ENDED).as_ref() {
+                Some(ended) => ended,
+                None        => global(&raw const ONLINE).as_ref().map_or(global(&raw const CTX), |online| &online.ctx)
+            };
+
+            // Only a game paused halfway through says so, not one that's over or yet to start.
+            let hud = Hud {
+                played: Some(PLAYED),
+                level: global(&raw const CAMPAIGN).as_ref().map(|campaign| (campaign.current + 1, campaign.levels.len())),
+                paused: PAUSED && !GAME_OVER && global(&raw const ENDED).is_none() && PLAYED > 0.
+            };
+
+            game_vertices(ctx, progress, &mut resulting_position, &mut colours);
+            particle_vertices(global(&raw const PARTICLES), &mut resulting_position, &mut colours);
+            hud_vertices(ctx, &hud, &mut resulting_position, &mut colours);
+
+            render(renderer.borrow_mut().as_mut(), &mut resulting_position, &mut colours);
+
+            request_animation_frame(f.borrow().as_ref().unwrap());
+        }));
+
+        request_animation_frame(g.borrow().as_ref().unwrap());
     }
 
-    false
+    Ok(())
 }
 
-// Head <-> apple collision
-fn did_the_snek_eat_the_apple(ctx: &Context) -> bool
+/// Picks what draws the board: WebGL 2 where the browser has it, the 2D canvas otherwise.
+/// Gives back the canvas drawn on, which isn't always the one passed in.
+fn create_renderer(canvas: web_sys::HtmlCanvasElement) -> Result<(web_sys::HtmlCanvasElement, Box<dyn Renderer>), JsValue>
 {
-    let Some(apple) = ctx.apple else { return false };
-    let apple_box = create_box(apple.0, apple.1, GRID_BOX_WIDTH, GRID_BOX_HEIGHT);
-    box_collision(&ctx.snake[0..12], &apple_box)
-}
+    let error = match WebGl::new(&canvas) {
+        Ok(webgl)  => return Ok((canvas, Box::new(webgl))),
+        Err(error) => error
+    };
 
-fn now() -> f64
-{
-    js_sys::Date::now()
+    log(&format!("Falling back to the 2D canvas: {}", error.as_string().unwrap_or_default()));
+    canvas_renderer(canvas)
 }
 
-fn create_box(x: f32, y: f32, width: f32, height: f32) -> Vec<f32>
+/// The 2D canvas renderer, for when WebGL isn't there or stopped working.
+fn canvas_renderer(canvas: web_sys::HtmlCanvasElement) -> Result<(web_sys::HtmlCanvasElement, Box<dyn Renderer>), JsValue>
 {
-    vec![
-        x,         y,
-        x + width, y,
-        x,         y + height,
+    // A canvas that handed out a WebGL context won't hand out a 2D one,
+    // so if WebGL failed after that the canvas is swapped for a fresh copy.
+    let canvas = match canvas.get_context("2d")?.is_some() {
+        true  => canvas,
+        false => {
+            let fresh = canvas.clone_node()?.dyn_into::<web_sys::HtmlCanvasElement>()?;
+            canvas.replace_with_with_node_1(&fresh)?;
+            fresh
+        }
+    };
 
-        x + width, y + height,
-        x,         y + height,
-        x + width, y
-    ]
+    let renderer = Box::new(Canvas2d::new(&canvas)?);
+    Ok((canvas, renderer))
 }
 
-/// For every registered key, creates an animation of the movement,
-/// if the key results in an animation.
-fn handle_key_action(ctx: &mut Context, animations: &mut Vec<Animation>, key: Direction)
+/// Sizes the canvas to the pixels it takes up on the screen, so it's sharp
+/// on high density screens too, and tells the renderer about it.
+fn fit_canvas(canvas: &web_sys::HtmlCanvasElement, renderer: &mut dyn Renderer)
 {
-    if !animations.is_empty() { return }
+    let ratio  = window().device_pixel_ratio();
+    let width  = (canvas.client_width() as f64 * ratio).round().max(1.) as u32;
+    let height = (canvas.client_height() as f64 * ratio).round().max(1.) as u32;
+
+    // Setting the size clears the canvas, even if it didn't change.
+    if canvas.width() != width || canvas.height() != height {
+        canvas.set_width(width);
+        canvas.set_height(height);
+    }
 
-    let resulting_position: Option<Vec<f32>> = match key {
-        // 87 | 119 | 38 /* w or up arrow */  => {
-        Direction::Up => {
-            if ctx.direction == Direction::Down { return }
+    renderer.resize(width, height);
+}
 
-            let mut end_position = ctx.snake[0..12].to_vec();
-            for i in (1..end_position.len()).step_by(2) {
-                end_position[i] = ((end_position[i]) / GRID_BOX_HEIGHT).round() * GRID_BOX_HEIGHT + STEP;
+/// Pauses the game while the browser has taken the WebGL context away,
+/// and carries on once it's back and the renderer is rebuilt.
+fn watch_context(canvas: &web_sys::HtmlCanvasElement, renderer: Rc<RefCell<Box<dyn Renderer>>>) -> Result<(), JsValue>
+{
+    let lost = Closure::<dyn FnMut(_)>::new(|event: web_sys::Event| {
+        // Otherwise the browser never gives it back.
+        event.prevent_default();
+        unsafe { context_lost(); }
+    });
+
+    let restored = {
+        let canvas = canvas.clone();
+
+        Closure::<dyn FnMut(_)>::new(move |_: web_sys::Event| {
+            let rebuilt = renderer.borrow_mut().restore();
+
+            // The game goes on either way, drawn on the 2D canvas if WebGL can't be had again.
+            if let Err(error) = rebuilt {
+                log(&format!("Failed to rebuild the renderer, falling back to the 2D canvas: {}", error));
+
+                match canvas_renderer(canvas.clone()) {
+                    Ok((canvas, mut fallback)) => {
+                        fit_canvas(&canvas, fallback.as_mut());
+                        unsafe { BACKEND = fallback.backend(); }
+                        *renderer.borrow_mut() = fallback;
+                    }
+                    Err(error) => log(&format!("Failed to fall back to the 2D canvas: {:?}", error))
+                }
             }
-            Some(move_snake(ctx, &ctx.snake, &end_position))
-        },
-        // 83 | 115 | 40 /* s or down arrow */ => {
-        Direction::Down => {
-            if ctx.direction == Direction::Up { return }
 
-            let mut end_position = ctx.snake[0..12].to_vec();
-            for i in (1..end_position.len()).step_by(2) {
-                end_position[i] = ((end_position[i]) / GRID_BOX_HEIGHT).round() * GRID_BOX_HEIGHT - STEP;
-            }
-            Some(move_snake(ctx, &ctx.snake, &end_position))
-        },
-        // 65 | 97 | 37 /* a or left arrow */ => {
-        Direction::Left => {
-            if ctx.direction == Direction::Right { return }
+            unsafe { context_restored() }
+        })
+    };
 
-            let mut end_position = ctx.snake[0..12].to_vec();
-            for i in (0..end_position.len()).step_by(2) {
-                end_position[i] = ((end_position[i]) / GRID_BOX_WIDTH).round() * GRID_BOX_WIDTH - STEP;
-            }
-            Some(move_snake(ctx, &ctx.snake, &end_position))
-        },
-        // 68 | 100 | 39 /* d or right arrow */ => {
-        Direction::Right => {
-            if ctx.direction == Direction::Left { return }
+    canvas.add_event_listener_with_callback("webglcontextlost", lost.as_ref().unchecked_ref())?;
+    canvas.add_event_listener_with_callback("webglcontextrestored", restored.as_ref().unchecked_ref())?;
 
-            let mut end_position = ctx.snake[0..12].to_vec();
-            for i in (0..end_position.len()).step_by(2) {
-                end_position[i] = ((end_position[i]) / GRID_BOX_WIDTH).round() * GRID_BOX_WIDTH + STEP;
-            }
-            Some(move_snake(ctx, &ctx.snake, &end_position))
-        },
-    };
+    // They're needed for as long as the page is open.
+    lost.forget();
+    restored.forget();
 
-    if let Some(resulting_position) = resulting_position {
-        ctx.direction = key;
-
-        unsafe {
-            let animation = Animation {
-                start_time: now(),
-                duration: ANIMATION_DURATION,
-                start_position: ctx.snake.clone(),
-                end_position: resulting_position,
-                is_paused: false,
-                pause_start_time: 0.,
-                pause_end_time: 0.
-            };
-            animations.push(animation);
-        }
-    }
+    Ok(())
 }
 
-/// Returns the movement direction between two snake parts.
-/// Or None I guess.
-fn movement_direction(previous: &[f32], next: &[f32]) -> Option<Direction>
+unsafe fn context_lost()
 {
-    let previous_x = (previous[0] / GRID_BOX_WIDTH).round() * GRID_BOX_WIDTH;
-    let previous_y = (previous[1] / GRID_BOX_HEIGHT).round() * GRID_BOX_HEIGHT;
-
-    let next_x = (next[0] / GRID_BOX_WIDTH).round() * GRID_BOX_WIDTH;
-    let next_y = (next[1] / GRID_BOX_HEIGHT).round() * GRID_BOX_HEIGHT;
-
-    let distance_x = previous_x - next_x;
-    let distance_y = previous_y - next_y;
+    let playing  = !PAUSED;
+    CONTEXT_LOST = Some(playing);
+    PAUSED       = true;
 
-    // Previous block is on the left part of the screen, while the current one is on the right.
-    if distance_x.abs() > GRID_BOX_WIDTH && previous_x < next_x {
-        return Some(Direction::Right)
+    for animation in global_mut(&raw mut QUEUED_ANIMATIONS).iter_mut() {
+        pause_animation(animation);
     }
 
-    // Previous block is on the right part of the screen, while the current one is on the left.
-    if distance_x.abs() > GRID_BOX_WIDTH && previous_x > next_x {
-        return Some(Direction::Left)
-    }
-
-    // Previous block is on the bottom part of the screen, while the current one is on the top.
-    if distance_y.abs() > GRID_BOX_HEIGHT && previous_y < next_y {
-        return Some(Direction::Up)
-    }
+    renderer_lost();
+}
 
-    // Previous block is on the top part of the screen, while the current one is on the bottom.
-    if distance_y.abs() > GRID_BOX_HEIGHT && previous_y > next_y {
-        return Some(Direction::Down)
-    }
+unsafe fn context_restored()
+{
+    let Some(playing) = global_mut(&raw mut CONTEXT_LOST).take() else { return };
+    PAUSED = !playing;
 
-    if previous_x == next_x && previous_y > next_y {
-        return Some(Direction::Up)
+    if playing {
+        for animation in global_mut(&raw mut QUEUED_ANIMATIONS).iter_mut() {
+            unpause_animation(animation);
+        }
     }
 
-    if previous_x == next_x && previous_y < next_y {
-        return Some(Direction::Down)
-    }
+    renderer_restored();
+}
 
-    if previous_y == next_y && previous_x > next_x {
-        return Some(Direction::Right)
+/// Draws the triangles and empties the lists for the next frame.
+fn render(renderer: &mut dyn Renderer, positions: &mut Vec<f32>, colours: &mut Vec<f32>)
+{
+    // Effects that can't be made are turned off rather than taking the game down with them.
+    if let Err(error) = renderer.set_effects(unsafe { global(&raw const EFFECTS).as_slice() }) {
+        log(&format!("Turning the effects off: {}", error));
+        unsafe { global_mut(&raw mut EFFECTS).clear(); }
     }
 
-    if previous_y == next_y && previous_x < next_x {
-        return Some(Direction::Left)
+    // A frame that can't be drawn is skipped, the next one may well work.
+    if let Err(error) = renderer.render(positions, colours) {
+        log(&format!("Skipping a frame: {}", error));
     }
 
-    None
+    positions.clear();
+    colours.clear();
 }
 
-/// Creates a vec containing the resulting position of
-/// a movement command.
-/// The resulting vec contains the positions of all
-/// parts of the snake.
-fn move_snake
-(
-    ctx: &Context,
-    snake: &[f32],
-    head_movement: &[f32]
-) -> Vec<f32>
+/// Steps the game played on this computer.
+unsafe fn local_step()
 {
-    let mut resulting_position = vec![0.; snake.len()];
-    let mut previous_position  = head_movement.to_vec();
+    let mut inputs = global(&raw const KEYS).each_ref().map(|keys| keys.last().copied());
+    if AUTOPILOT { inputs[0] = autopilot(global(&raw const CTX), 0); }
 
-    for part in (0..snake.len()).step_by(12) {
-        // Apply the movement and direction using the delta between the
-        // current and the previous block.
+    let events = step(global_mut(&raw mut CTX), &inputs);
+    global_mut(&raw mut PARTICLES).react(global(&raw const CTX), &events);
 
-        // Do mutations to 'previous_position' with correct direction.
-        match movement_direction(&previous_position, &snake[part..part + 12]) {
-            Some(Direction::Up) => {
-                for i in (1..previous_position.len()).step_by(2) {
-                    previous_position[i] = ((snake[part + i]) / GRID_BOX_HEIGHT).round() * GRID_BOX_HEIGHT + STEP;
-                }
-            }
-            Some(Direction::Down) => {
-                for i in (1..previous_position.len()).step_by(2) {
-                    previous_position[i] = ((snake[part + i]) / GRID_BOX_HEIGHT).round() * GRID_BOX_HEIGHT - STEP;
-                }
-            }
-            Some(Direction::Left) => {
-                for i in (0..previous_position.len()).step_by(2) {
-                    previous_position[i] = ((snake[part + i]) / GRID_BOX_WIDTH).round() * GRID_BOX_WIDTH - STEP;
-                }
-            }
-            Some(Direction::Right) => {
-                for i in (0..previous_position.len()).step_by(2) {
-                    previous_position[i] = ((snake[part + i]) / GRID_BOX_WIDTH).round() * GRID_BOX_WIDTH + STEP;
-                }
+    broadcast_frame();
+
+    if events.apple_expired {
+        apple_expired();
+    }
+
+    if let Some(player) = events.ate {
+        let score   = CTX.snakes[player].score;
+        let reached = CTX.target.is_some_and(|target| score >= target);
+
+        match (PLAYERS, reached) {
+            (1, false) => (),
+            (1, true)  => complete_level(score),
+            (_, false) => player_scored(player, score),
+            (_, true)  => {
+                player_scored(player, score);
+                end_versus(Some(player));
             }
-            None => { }
         }
+    }
 
-        resulting_position[part..part + 12].copy_from_slice(&previous_position);
-        block_exceeds_screen_edge
-        (
-            ctx,
-            &mut previous_position,
-            &mut resulting_position
-        );
+    if !events.died.is_empty() {
+        // Dying while playtesting just restarts the level.
+        // With two players the last snake standing wins.
+        match (PLAYERS, global(&raw const EDITOR).as_ref().is_some_and(|e| e.playtesting)) {
+            (1, true)  => (),
+            (1, false) => game_over(CTX.snakes[0].score),
+            _ => versus_over(survivor(global(&raw const CTX), &events.died))
+        }
 
-        previous_position = snake[part..part + 12].to_vec();
+        GAME_OVER = true;
+        ENDED     = Some(global(&raw const CTX).clone());
+        initiate_game();
     }
 
-    resulting_position
+    if !PAUSED {
+        global_mut(&raw mut QUEUED_ANIMATIONS).push(Animation::new(step_duration(global(&raw const CTX))));
+    }
 }
 
-// GL Code
-
-fn draw_vertices
-(
-    context: &WebGl2RenderingContext,
-    program: &WebGlProgram,
-    vertices: &[f32],
-    colours: &[f32]
-) -> Result<(), String>
+/// Steps the online game. When it has to wait for the other player
+/// no animation is queued, so this is tried again every frame.
+unsafe fn online_step(online: &mut Rollback)
 {
-    let vao = context.create_vertex_array().ok_or("Failed to create vertex array object")?;
-    context.bind_vertex_array(Some(&vao));
-
-    // Position
-
-    {
-        let buffer = context.create_buffer().ok_or("Failed to create buffer")?;
-        context.bind_buffer(WebGl2RenderingContext::ARRAY_BUFFER, Some(&buffer));
+    let input = match AUTOPILOT {
+        true  => autopilot(&online.ctx, online.player),
+        false => KEYS[online.player].last().copied()
+    };
+    let desynced = online.desync().is_some();
 
-        let array_buf = js_sys::Float32Array::new_with_length(vertices.len() as u32);
-        array_buf.copy_from(vertices);
+    let events = online.advance(input);
 
-        context.buffer_data_with_array_buffer_view
-        (
-            WebGl2RenderingContext::ARRAY_BUFFER,
-            &array_buf,
-            WebGl2RenderingContext::DYNAMIC_DRAW,
-        );
+    send_messages(online);
+    report_desync(online, desynced);
 
-        let attrib_location = context.get_attrib_location(program, "position") as u32;
-        context.vertex_attrib_pointer_with_i32
-        (
-            attrib_location, 2, WebGl2RenderingContext::FLOAT, false, 0, 0
-        );
-        context.enable_vertex_attrib_array(attrib_location);
+    if let Some(events) = events.as_ref() {
+        global_mut(&raw mut PARTICLES).react(&online.ctx, events);
     }
 
-    // Colour
-
-    {
-        let buffer = context.create_buffer().ok_or("Failed to create buffer")?;
-        context.bind_buffer(WebGl2RenderingContext::ARRAY_BUFFER, Some(&buffer));
-
-        let array_buf = js_sys::Float32Array::new_with_length(colours.len() as u32);
-        array_buf.copy_from(colours);
-
-        context.buffer_data_with_array_buffer_view
-        (
-            WebGl2RenderingContext::ARRAY_BUFFER,
-            &array_buf,
-            WebGl2RenderingContext::DYNAMIC_DRAW,
-        );
+    if let Some(player) = events.as_ref().and_then(|events| events.ate) {
+        player_scored(player, online.ctx.snakes[player].score);
+    }
 
-        let attrib_location = context.get_attrib_location(program, "vertexColour") as u32;
-        context.vertex_attrib_pointer_with_i32
-        (
-            attrib_location, 3, WebGl2RenderingContext::FLOAT, false, 0, 0
-        );
-        context.enable_vertex_attrib_array(attrib_location);
+    // The end is only shown once it can't be undone by a late input.
+    if let (false, Some(winner)) = (GAME_OVER, online.result()) {
+        GAME_OVER = true;
+        PAUSED    = true;
+        return versus_over(winner)
     }
 
-    Ok(())
+    if events.is_some() {
+        global_mut(&raw mut QUEUED_ANIMATIONS).push(Animation::new(step_duration(&online.ctx)));
+    }
 }
 
-pub fn compile_shader
-(
-    context: &WebGl2RenderingContext,
-    shader_type: u32,
-    source: &str
-) -> Result<WebGlShader, String>
+unsafe fn send_messages(online: &mut Rollback)
 {
-    let shader = context
-        .create_shader(shader_type)
-        .ok_or_else(|| String::from("Unable to create shader object"))?;
-
-    context.shader_source(&shader, source);
-    context.compile_shader(&shader);
-
-    let compiled = context
-        .get_shader_parameter(&shader, WebGl2RenderingContext::COMPILE_STATUS)
-        .as_bool()
-        .unwrap_or(false);
-
-    match compiled {
-        true  => Ok(shader),
-        false => Err(
-            context
-                .get_shader_info_log(&shader)
-                .unwrap_or_else(|| String::from("Unknown error creating shader"))
-        )
+    for message in online.messages() {
+        net_send(&message.encode());
     }
 }
 
-pub fn link_program
-(
-    context: &WebGl2RenderingContext,
-    vertex_shader: &WebGlShader,
-    fragment_shader: &WebGlShader,
-) -> Result<WebGlProgram, String>
+/// Tells JS about a desync, unless it already knew about one.
+unsafe fn report_desync(online: &Rollback, already_desynced: bool)
 {
-    let program = context
-        .create_program()
-        .ok_or_else(|| String::from("Unable to create program"))?;
+    if let (false, Some(tick)) = (already_desynced, online.desync()) {
+        net_desync(tick);
+    }
+}
 
-    context.attach_shader(&program, vertex_shader);
-    context.attach_shader(&program, fragment_shader);
+pub fn terrain_colour(terrain: Terrain) -> [f32; 3]
+{
+    match terrain {
+        Terrain::Ice         => ICE_COLOUR,
+        Terrain::Mud         => MUD_COLOUR,
+        Terrain::Conveyor(_) => CONVEYOR_COLOUR,
+        Terrain::Gate(_)     => GATE_COLOUR
+    }
+}
 
-    context.link_program(&program);
+fn window() -> web_sys::Window
+{
+    web_sys::window().expect("no global `window` exists")
+}
 
-    let program_linked = context
-        .get_program_parameter(&program, WebGl2RenderingContext::LINK_STATUS)
-        .as_bool()
-        .unwrap_or(false);
+fn request_animation_frame(f: &Closure<dyn FnMut()>)
+{
+    window()
+        .request_animation_frame(f.as_ref().unchecked_ref())
+        .expect("should register `requestAnimationFrame` OK");
+}
 
-    match program_linked {
-        true  => Ok(program),
-        false => Err(
-            context.get_program_info_log(&program)
-                .unwrap_or_else(|| String::from("Unknown error creating program object"))
-        )
-    }
+fn now() -> f64
+{
+    js_sys::Date::now()
 }
 
 // Utility
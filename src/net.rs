@@ -0,0 +1,204 @@
+use std::collections::{HashMap, VecDeque};
+
+use crate::Direction;
+use crate::game::{Context, snapshot};
+
+/// How many ticks ahead an input is scheduled. Gives the input
+/// that much time to reach the other player before it's needed.
+pub const INPUT_DELAY: usize = 3;
+
+/// Both players send a hash of their game every this many ticks.
+pub const HASH_INTERVAL: usize = 30;
+
+/// Messages sent between the players, through the relay.
+///
+/// Every message is a single line of text, with the fields separated by spaces:
+///
+/// ```text
+/// join 1 3735928559 0
+/// input 42 1 left
+/// hash 60 9f0c3e5d71a2b4c8
+/// over 120 0
+/// rematch
+/// ```
+#[derive(PartialEq, Clone, Debug)]
+pub enum Message {
+    /// Sent by the relay once both players are in. Tells which player
+    /// this is, and the seed and built-in level both games start from.
+    Join { player: usize, seed: u64, level: usize },
+    /// The input of a player for the given tick, `none` if it didn't turn.
+    Input { tick: usize, player: usize, direction: Option<Direction> },
+    /// Hash of the whole game after the given tick.
+    Hash { tick: usize, hash: u64 },
+    /// The game ended on the given tick. No winner is a draw.
+    GameOver { tick: usize, winner: Option<usize> },
+    /// Asks for another game once this one is over. The relay starts it
+    /// with a new `join` once both players asked.
+    Rematch
+}
+
+impl Message
+{
+    pub fn encode(&self) -> String
+    {
+        match self {
+            Message::Join { player, seed, level } => format!("join {} {} {}", player, seed, level),
+            Message::Input { tick, player, direction } => format!
+            (
+                "input {} {} {}",
+                tick,
+                player,
+                direction.map(Direction::name).unwrap_or("none")
+            ),
+            Message::Hash { tick, hash } => format!("hash {} {:016x}", tick, hash),
+            Message::GameOver { tick, winner } => match winner {
+                Some(winner) => format!("over {} {}", tick, winner),
+                None         => format!("over {} draw", tick)
+            },
+            Message::Rematch => String::from("rematch")
+        }
+    }
+
+    /// Reads a message back. Anything malformed gives nothing.
+    pub fn decode(text: &str) -> Option<Message>
+    {
+        let fields: Vec<&str> = text.split_whitespace().collect();
+
+        let message = match fields[..] {
+            ["join", player, seed, level] => Message::Join {
+                player: player.parse().ok()?,
+                seed: seed.parse().ok()?,
+                level: level.parse().ok()?
+            },
+            ["input", tick, player, direction] => Message::Input {
+                tick: tick.parse().ok()?,
+                player: player.parse().ok()?,
+                direction: match direction {
+                    "none" => None,
+                    name   => Some(Direction::from_name(name)?)
+                }
+            },
+            ["hash", tick, hash] => Message::Hash {
+                tick: tick.parse().ok()?,
+                hash: u64::from_str_radix(hash, 16).ok()?
+            },
+            ["over", tick, winner] => Message::GameOver {
+                tick: tick.parse().ok()?,
+                winner: match winner {
+                    "draw" => None,
+                    winner => Some(winner.parse().ok()?)
+                }
+            },
+            ["rematch"] => Message::Rematch,
+            _ => return None
+        };
+
+        Some(message)
+    }
+}
+
+/// Hash of the game after the last tick. Two games that hash the same are in sync.
+pub fn state_hash(ctx: &Context) -> u64
+{
+    snapshot(ctx).hash()
+}
+
+/// Passes messages between two games in the same process, each one arriving
+/// `latency` ticks after it was sent. Lets netcode be tried out without a network.
+#[derive(Debug, Default)]
+pub struct MemoryTransport
+{
+    pub latency: usize,
+
+    /// Messages on their way, with the tick they arrive on.
+    in_flight: VecDeque<(usize, Message)>
+}
+
+impl MemoryTransport
+{
+    pub fn new(latency: usize) -> MemoryTransport
+    {
+        MemoryTransport { latency, in_flight: VecDeque::new() }
+    }
+
+    pub fn send(&mut self, now: usize, message: Message)
+    {
+        self.in_flight.push_back((now + self.latency, message));
+    }
+
+    /// Every message that has arrived by `now`, in the order they were sent.
+    pub fn receive(&mut self, now: usize) -> Vec<Message>
+    {
+        let arrived = self.in_flight.iter().take_while(|(arrival, _)| *arrival <= now).count();
+        self.in_flight.drain(..arrived).map(|(_, message)| message).collect()
+    }
+}
+
+/// Compares the hashes and results of both copies of a game
+/// as they come in, remembering the first tick they differ on.
+#[derive(Debug, Default)]
+pub(crate) struct SyncCheck
+{
+    hashes: HashMap<usize, u64>,
+    remote_hashes: HashMap<usize, u64>,
+
+    /// How the game ended here and on the other side, with the tick it ended on.
+    result: Option<(usize, Option<usize>)>,
+    remote_result: Option<(usize, Option<usize>)>,
+
+    pub desync: Option<usize>
+}
+
+impl SyncCheck
+{
+    pub fn hash(&mut self, tick: usize, hash: u64)
+    {
+        self.hashes.insert(tick, hash);
+        self.compare(tick);
+    }
+
+    pub fn remote_hash(&mut self, tick: usize, hash: u64)
+    {
+        self.remote_hashes.insert(tick, hash);
+        self.compare(tick);
+    }
+
+    pub fn result(&mut self, tick: usize, winner: Option<usize>)
+    {
+        self.result = Some((tick, winner));
+        self.compare_results();
+    }
+
+    pub fn remote_result(&mut self, tick: usize, winner: Option<usize>)
+    {
+        self.remote_result = Some((tick, winner));
+        self.compare_results();
+    }
+
+    pub fn finished(&self) -> bool
+    {
+        self.result.is_some()
+    }
+
+    fn compare(&mut self, tick: usize)
+    {
+        let (Some(hash), Some(remote)) = (self.hashes.get(&tick), self.remote_hashes.get(&tick)) else { return };
+
+        if hash != remote { self.desynced(tick); }
+
+        self.hashes.remove(&tick);
+        self.remote_hashes.remove(&tick);
+    }
+
+    fn compare_results(&mut self)
+    {
+        let (Some(result), Some(remote)) = (self.result, self.remote_result) else { return };
+        if result != remote { self.desynced(result.0.min(remote.0)); }
+    }
+
+    fn desynced(&mut self, tick: usize)
+    {
+        self.desync = Some(self.desync.map_or(tick, |desync| desync.min(tick)));
+    }
+}
+
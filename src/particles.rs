@@ -0,0 +1,164 @@
+use std::f32::consts::TAU;
+
+use crate::{APPLE_COLOUR, GRID_BOX_HEIGHT, GRID_BOX_WIDTH, SCENE_HEIGHT, SCENE_WIDTH, SECOND_SNAKE_COLOUR, SNAKE_COLOUR};
+use crate::game::{Cell, Context, Events};
+use crate::rng::Rng;
+
+/// The most particles around at once. Any more just aren't made.
+pub const MAX_PARTICLES: usize = 400;
+
+/// How many steps a snake leaves a trail for after it got faster.
+const TRAIL_STEPS: usize = 6;
+
+#[derive(Clone, Debug)]
+pub struct Particle
+{
+    /// Where it is in the scene, like the vertices.
+    pub position: (f32, f32),
+    /// In scene pixels a second.
+    pub velocity: (f32, f32),
+    pub colour: [f32; 3],
+    pub size: f32,
+    /// How long it's been around and how long it lasts, in seconds.
+    pub age: f32,
+    pub lifetime: f32
+}
+
+/// Sparks thrown off by what happens in the game: a burst where an apple is
+/// eaten, an explosion along a snake that died, and a trail behind the snakes
+/// for a few steps after they get faster.
+///
+/// They're kept apart from the game. Only the time that passes moves them,
+/// so when that stops with the game, they stop too.
+#[derive(Clone, Debug)]
+pub struct Particles
+{
+    pub particles: Vec<Particle>,
+    /// Steps left of the trail.
+    trail: usize,
+    /// How long a step took the last time, to tell when the game got faster.
+    tick_duration: f64,
+    rng: Rng
+}
+
+impl Particles
+{
+    pub const fn new(seed: u64) -> Particles
+    {
+        Particles { particles: Vec::new(), trail: 0, tick_duration: 0., rng: Rng::new(seed) }
+    }
+
+    pub fn is_empty(&self) -> bool
+    {
+        self.particles.is_empty()
+    }
+
+    /// Throws off particles for what happened in a step. Has to be called
+    /// before a snake that died is taken off the board.
+    pub fn react(&mut self, ctx: &Context, events: &Events)
+    {
+        if let Some(player) = events.ate {
+            let at = centre(ctx.snakes[player].body[0]);
+
+            for _ in 0..24 {
+                let colour = match self.rng.chance(0.3) {
+                    true  => [1., 0.9, 0.6],
+                    false => APPLE_COLOUR
+                };
+                self.spawn(at, 260., colour, 9., 0.6);
+            }
+        }
+
+        for &(player, _) in &events.died {
+            let colour = snake_colour(player);
+            let body   = &ctx.snakes[player].body;
+
+            // Long snakes get fewer per cell, so the cap doesn't cut off the end of the tail.
+            let per_cell = (MAX_PARTICLES.saturating_sub(self.particles.len()) / body.len()).clamp(1, 6);
+
+            for &cell in body {
+                for _ in 0..per_cell {
+                    self.spawn(centre(cell), 380., colour, 12., 1.2);
+                }
+            }
+        }
+
+        if self.tick_duration > 0. && ctx.tick_duration < self.tick_duration {
+            self.trail = TRAIL_STEPS;
+        }
+        self.tick_duration = ctx.tick_duration;
+
+        if self.trail == 0 { return }
+        self.trail -= 1;
+
+        // Left in the cell the tail just moved out of, so it's not hidden under the snake.
+        for (player, snake) in ctx.snakes.iter().enumerate() {
+            let colour = snake_colour(player).map(|channel| (channel * 1.4).min(1.));
+
+            for _ in 0..3 {
+                self.spawn(centre(snake.previous[snake.previous.len() - 1]), 40., colour, 6., 0.4);
+            }
+        }
+    }
+
+    /// Moves every particle on by `seconds`, and lets go of the ones that are done
+    /// or have left the board.
+    pub fn update(&mut self, seconds: f32)
+    {
+        // They slow down, so bursts spread out and then hang in the air for a bit.
+        let drag = (1. - 2.5 * seconds).max(0.);
+
+        for particle in &mut self.particles {
+            particle.age        += seconds;
+            particle.position.0 += particle.velocity.0 * seconds;
+            particle.position.1 += particle.velocity.1 * seconds;
+            particle.velocity.0 *= drag;
+            particle.velocity.1 *= drag;
+        }
+
+        self.particles.retain(|particle| {
+            let (x, y) = particle.position;
+            particle.age < particle.lifetime && (0. ..SCENE_WIDTH).contains(&x) && (0. ..SCENE_HEIGHT).contains(&y)
+        });
+    }
+
+    /// A particle off in a random direction, with a bit of randomness
+    /// to how fast it goes and how long it lasts.
+    fn spawn(&mut self, at: (f32, f32), speed: f32, colour: [f32; 3], size: f32, lifetime: f32)
+    {
+        if self.particles.len() >= MAX_PARTICLES { return }
+
+        let angle = self.unit() * TAU;
+        let speed = speed * (0.3 + 0.7 * self.unit());
+
+        let particle = Particle {
+            position: at,
+            velocity: (angle.cos() * speed, angle.sin() * speed),
+            colour,
+            size,
+            age: 0.,
+            lifetime: lifetime * (0.6 + 0.4 * self.unit())
+        };
+
+        self.particles.push(particle);
+    }
+
+    /// A number in `0..1`.
+    fn unit(&mut self) -> f32
+    {
+        (self.rng.next_u64() >> 40) as f32 / (1u64 << 24) as f32
+    }
+}
+
+fn centre((x, y): Cell) -> (f32, f32)
+{
+    ((x as f32 + 0.5) * GRID_BOX_WIDTH, (y as f32 + 0.5) * GRID_BOX_HEIGHT)
+}
+
+fn snake_colour(player: usize) -> [f32; 3]
+{
+    match player {
+        0 => SNAKE_COLOUR,
+        _ => SECOND_SNAKE_COLOUR
+    }
+}
@@ -0,0 +1,315 @@
+use std::fmt;
+
+use wasm_bindgen::prelude::*;
+
+use crate::{BACKGROUND_COLOUR, SCENE_HEIGHT, SCENE_WIDTH};
+
+/// Something the board can be drawn on.
+///
+/// The board is drawn as a list of triangles. `positions` holds two
+/// coordinates for every vertex, in pixels from the bottom left corner
+/// of the scene, and `colours` three channels for every vertex.
+pub trait Renderer
+{
+    /// The name of the way it draws, reported to the page.
+    fn backend(&self) -> &'static str;
+
+    /// Makes again whatever the renderer keeps outside of memory it owns,
+    /// after the browser threw it away. Nothing to do for most of them.
+    fn restore(&mut self) -> Result<(), String>
+    {
+        Ok(())
+    }
+
+    /// Draws the effects over everything from now on, in the order given.
+    /// Only some renderers can, the rest leave the board as it is.
+    fn set_effects(&mut self, _effects: &[Effect]) -> Result<(), String>
+    {
+        Ok(())
+    }
+
+    /// Draws with shaders from outside instead of the built-in ones. They need the
+    /// same `position` and `vertexColour` attributes and `resolution` uniform.
+    /// If they don't work the built-in ones are used again.
+    fn load_shaders(&mut self, _vertex: &str, _fragment: &str) -> Result<(), ShaderError>
+    {
+        Err(ShaderError::new("renderer", format!("The {} renderer has no shaders", self.backend())))
+    }
+
+    /// Tells the renderer how many pixels the canvas has now. The scene is
+    /// drawn as big as fits in them, see `letterbox`.
+    fn resize(&mut self, width: u32, height: u32);
+
+    /// Clears everything to the background colour and draws the triangles.
+    fn render(&mut self, positions: &[f32], colours: &[f32]) -> Result<(), String>;
+}
+
+/// Why shaders from outside couldn't be used.
+#[wasm_bindgen(getter_with_clone)]
+#[derive(PartialEq, Clone, Debug)]
+pub struct ShaderError
+{
+    /// `vertex` or `fragment` if it didn't compile, `link` if the two don't
+    /// go together, `interface` if something the board needs is missing,
+    /// and `renderer` if the board isn't drawn with shaders at all.
+    pub stage: String,
+    /// The line in the source the compiler complained about, if it said.
+    pub line: Option<usize>,
+    pub message: String,
+    /// Everything the compiler said.
+    pub log: String
+}
+
+impl ShaderError
+{
+    pub fn new(stage: &str, message: impl Into<String>) -> ShaderError
+    {
+        let message = message.into();
+        ShaderError { stage: String::from(stage), line: None, log: message.clone(), message }
+    }
+
+    /// Picks the first error out of a compiler log. They mostly look like
+    /// `ERROR: 0:12: 'colour' : undeclared identifier`, the 12 being the line.
+    pub fn from_log(stage: &str, log: &str) -> ShaderError
+    {
+        let first = log
+            .lines()
+            .map(str::trim)
+            .find(|line| line.starts_with("ERROR:"))
+            .or_else(|| log.lines().map(str::trim).find(|line| !line.is_empty()))
+            .unwrap_or("Unknown error");
+
+        let parts: Vec<&str> = first.trim_start_matches("ERROR:").splitn(3, ':').collect();
+
+        let (line, message) = match parts[..] {
+            [_, line, message] => match line.trim().parse() {
+                Ok(line) => (Some(line), message.trim()),
+                Err(_)   => (None, first)
+            },
+            _ => (None, first)
+        };
+
+        ShaderError { stage: String::from(stage), line, message: String::from(message), log: String::from(log) }
+    }
+}
+
+impl fmt::Display for ShaderError
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+    {
+        match self.line {
+            Some(line) => write!(f, "{} shader, line {}: {}", self.stage, line, self.message),
+            None       => write!(f, "{} shader: {}", self.stage, self.message)
+        }
+    }
+}
+
+/// Post-processing drawn over the finished board. They're always applied
+/// in the order they're declared in, however they were turned on.
+#[derive(PartialEq, Eq, PartialOrd, Ord, Copy, Clone, Debug)]
+pub enum Effect
+{
+    /// The apple lights up its surroundings.
+    Bloom,
+    /// Red and blue come apart towards the edges.
+    ChromaticAberration,
+    /// Scanlines and the curved glass of an old screen.
+    Crt,
+    /// Darker towards the corners.
+    Vignette
+}
+
+impl Effect
+{
+    pub const ALL: [Effect; 4] = [Effect::Bloom, Effect::ChromaticAberration, Effect::Crt, Effect::Vignette];
+
+    pub fn from_name(name: &str) -> Option<Effect>
+    {
+        Effect::ALL.into_iter().find(|effect| effect.name() == name)
+    }
+
+    pub fn name(self) -> &'static str
+    {
+        match self {
+            Effect::Bloom               => "bloom",
+            Effect::ChromaticAberration => "chromatic-aberration",
+            Effect::Crt                 => "crt",
+            Effect::Vignette            => "vignette"
+        }
+    }
+}
+
+/// Draws the triangles on the CPU, into an RGBA image in memory.
+///
+/// Pixels are covered the way a GPU covers them: a pixel is in a triangle
+/// if its centre is, and centres right on an edge shared by two triangles
+/// only go to one of them, so boxes made of two triangles have no seams.
+/// Colours are interpolated between the vertices.
+#[derive(Clone, Debug)]
+pub struct Rasteriser
+{
+    pub width: usize,
+    pub height: usize,
+    /// The size of the scene the positions are in.
+    pub resolution: (f32, f32),
+    /// The part of the image the scene is drawn in, see `letterbox`. Left, bottom, width and height.
+    pub viewport: [usize; 4],
+    /// Four bytes per pixel, row by row from the top. Outside of the viewport they're transparent.
+    pub pixels: Vec<u8>
+}
+
+impl Rasteriser
+{
+    pub fn new(width: usize, height: usize, resolution: (f32, f32)) -> Rasteriser
+    {
+        let (x, y, fitted_width, fitted_height) = letterbox(width as f64, height as f64);
+        let [x, y, fitted_width, fitted_height] = [x, y, fitted_width, fitted_height].map(|value| value.round() as usize);
+
+        // Rounding both sides up mustn't take it past the edge.
+        let viewport = [x, y, fitted_width.min(width - x), fitted_height.min(height - y)];
+
+        Rasteriser { width, height, resolution, viewport, pixels: vec![0; width * height * 4] }
+    }
+
+    /// The colour of a pixel, counting rows from the top.
+    pub fn pixel(&self, x: usize, y: usize) -> [u8; 4]
+    {
+        let i = (y * self.width + x) * 4;
+        [self.pixels[i], self.pixels[i + 1], self.pixels[i + 2], self.pixels[i + 3]]
+    }
+
+    /// Clears the viewport to `colour`, and everything around it to nothing.
+    fn clear(&mut self, colour: [f32; 3])
+    {
+        let [r, g, b] = colour.map(channel);
+        let [left, bottom, width, height] = self.viewport;
+
+        self.pixels.fill(0);
+
+        for y in bottom..bottom + height {
+            let row = (self.height - 1 - y) * self.width;
+
+            for pixel in self.pixels[(row + left) * 4..(row + left + width) * 4].chunks_exact_mut(4) {
+                pixel.copy_from_slice(&[r, g, b, 255]);
+            }
+        }
+    }
+
+    fn triangle(&mut self, vertices: [(f32, f32); 3], colours: [[f32; 3]; 3])
+    {
+        // From scene coordinates to pixels, the same as the vertex shader followed by the viewport.
+        let [left, bottom, width, height] = self.viewport.map(|value| value as f32);
+        let [a, b, c] = vertices.map(|(x, y)| {
+            (left + x / self.resolution.0 * width, bottom + y / self.resolution.1 * height)
+        });
+
+        let area = edge(a, b, c);
+        if area == 0. { return }
+
+        // Winding doesn't matter, both sides are drawn.
+        let (b, c, colours) = match area > 0. {
+            true  => (b, c, colours),
+            false => (c, b, [colours[0], colours[2], colours[1]])
+        };
+        let area = area.abs();
+
+        // Nothing is drawn outside of the viewport, like with the scissor test.
+        let min_x = (a.0.min(b.0).min(c.0).floor().max(0.) as usize).max(self.viewport[0]);
+        let min_y = (a.1.min(b.1).min(c.1).floor().max(0.) as usize).max(self.viewport[1]);
+        let max_x = (a.0.max(b.0).max(c.0).ceil().max(0.) as usize).min(self.viewport[0] + self.viewport[2]);
+        let max_y = (a.1.max(b.1).max(c.1).ceil().max(0.) as usize).min(self.viewport[1] + self.viewport[3]);
+
+        for y in min_y..max_y {
+            for x in min_x..max_x {
+                let centre = (x as f32 + 0.5, y as f32 + 0.5);
+
+                let weights = [edge(b, c, centre), edge(c, a, centre), edge(a, b, centre)];
+                let edges   = [(b, c), (c, a), (a, b)];
+
+                let inside = weights
+                    .iter()
+                    .zip(edges)
+                    .all(|(&weight, (from, to))| weight > 0. || (weight == 0. && top_left(from, to)));
+
+                if !inside { continue }
+
+                let colour: [f32; 3] = std::array::from_fn(|c| {
+                    (0..3).map(|i| colours[i][c] * weights[i]).sum::<f32>() / area
+                });
+
+                // Rows are stored from the top, the scene counts them from the bottom.
+                let i = ((self.height - 1 - y) * self.width + x) * 4;
+                let [red, green, blue] = colour.map(channel);
+                self.pixels[i..i + 4].copy_from_slice(&[red, green, blue, 255]);
+            }
+        }
+    }
+}
+
+impl Renderer for Rasteriser
+{
+    fn backend(&self) -> &'static str
+    {
+        "software"
+    }
+
+    /// Starts over with an image of the new size.
+    fn resize(&mut self, width: u32, height: u32)
+    {
+        *self = Rasteriser::new(width as usize, height as usize, self.resolution);
+    }
+
+    fn render(&mut self, positions: &[f32], colours: &[f32]) -> Result<(), String>
+    {
+        if positions.len() / 2 != colours.len() / 3 {
+            return Err(format!("{} vertices but {} colours", positions.len() / 2, colours.len() / 3))
+        }
+
+        self.clear(BACKGROUND_COLOUR);
+
+        let vertices: Vec<(f32, f32)> = positions.chunks_exact(2).map(|p| (p[0], p[1])).collect();
+        let colours: Vec<[f32; 3]>    = colours.chunks_exact(3).map(|c| [c[0], c[1], c[2]]).collect();
+
+        for (vertices, colours) in vertices.chunks_exact(3).zip(colours.chunks_exact(3)) {
+            self.triangle([vertices[0], vertices[1], vertices[2]], [colours[0], colours[1], colours[2]]);
+        }
+
+        Ok(())
+    }
+}
+
+/// The biggest part of a `width` by `height` screen the scene fits in without
+/// being stretched, in the middle of it. Gives its left and bottom edges and
+/// its size, in whatever units the screen was measured in.
+pub fn letterbox(width: f64, height: f64) -> (f64, f64, f64, f64)
+{
+    let scale = (width / SCENE_WIDTH as f64).min(height / SCENE_HEIGHT as f64);
+
+    let fitted_width  = SCENE_WIDTH as f64 * scale;
+    let fitted_height = SCENE_HEIGHT as f64 * scale;
+
+    ((width - fitted_width) / 2., (height - fitted_height) / 2., fitted_width, fitted_height)
+}
+
+/// Twice the signed area of the triangle `a`, `b`, `p`.
+/// Positive if `p` is to the left of the edge going from `a` to `b`.
+fn edge(a: (f32, f32), b: (f32, f32), p: (f32, f32)) -> f32
+{
+    (b.0 - a.0) * (p.1 - a.1) - (b.1 - a.1) * (p.0 - a.0)
+}
+
+/// Whether an edge of an anticlockwise triangle is a top or a left one.
+/// Pixel centres right on the edge belong to the triangle only then,
+/// so two triangles sharing an edge never both cover them.
+fn top_left(from: (f32, f32), to: (f32, f32)) -> bool
+{
+    let (dx, dy) = (to.0 - from.0, to.1 - from.1);
+
+    // With y going up, a top edge is flat and goes left, a left edge goes down.
+    (dy == 0. && dx < 0.) || dy < 0.
+}
+
+fn channel(value: f32) -> u8
+{
+    (value.clamp(0., 1.) * 255.).round() as u8
+}
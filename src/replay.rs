@@ -0,0 +1,96 @@
+use crate::Direction;
+use crate::bot::Strategy;
+use crate::game::Context;
+
+/// Everything needed to play a single player game again, tick for tick:
+/// the seed it started from and every turn the player made.
+/// The level isn't part of it, the replay has to be played on the same one.
+///
+/// Replays are written as text, the seed first and then a turn per line,
+/// with the tick it was made on:
+///
+/// ```text
+/// seed 3735928559
+/// 4 up
+/// 9 left
+/// 17 down
+/// ```
+#[derive(PartialEq, Clone, Debug, Default)]
+pub struct Replay
+{
+    pub seed: u64,
+    /// Turns by tick, in order. Ticks without a turn went straight on.
+    pub inputs: Vec<(usize, Direction)>
+}
+
+impl Replay
+{
+    pub fn new(seed: u64) -> Replay
+    {
+        Replay { seed, inputs: vec![] }
+    }
+
+    /// Remembers the input for a tick. Going the way the snake already is isn't a turn.
+    pub fn record(&mut self, ctx: &Context, input: Direction)
+    {
+        if input == ctx.snakes[0].direction { return }
+        self.inputs.push((ctx.ticks, input));
+    }
+
+    /// The input for a tick, nothing if the player didn't turn.
+    pub fn input(&self, tick: usize) -> Option<Direction>
+    {
+        self.inputs
+            .binary_search_by_key(&tick, |&(tick, _)| tick)
+            .ok()
+            .map(|i| self.inputs[i].1)
+    }
+
+    pub fn encode(&self) -> String
+    {
+        let mut text = format!("seed {}\n", self.seed);
+
+        for (tick, direction) in &self.inputs {
+            text.push_str(&format!("{} {}\n", tick, direction.name()));
+        }
+
+        text
+    }
+
+    /// Reads a replay back. Anything malformed, or turns out of order, gives nothing.
+    pub fn decode(text: &str) -> Option<Replay>
+    {
+        let mut lines = text.lines().map(str::trim).filter(|line| !line.is_empty());
+
+        let seed = match lines.next()?.split_whitespace().collect::<Vec<_>>()[..] {
+            ["seed", seed] => seed.parse().ok()?,
+            _ => return None
+        };
+
+        let inputs: Vec<(usize, Direction)> = lines
+            .map(|line| match line.split_whitespace().collect::<Vec<_>>()[..] {
+                [tick, direction] => Some((tick.parse().ok()?, Direction::from_name(direction)?)),
+                _ => None
+            })
+            .collect::<Option<_>>()?;
+
+        if !inputs.windows(2).all(|pair| pair[0].0 < pair[1].0) { return None }
+
+        Some(Replay { seed, inputs })
+    }
+}
+
+/// Plays the recorded turns back. Only makes sense on a game
+/// started from the same level and seed as the recorded one.
+impl Strategy for Replay
+{
+    fn name(&self) -> String
+    {
+        String::from("replay")
+    }
+
+    fn next_move(&mut self, ctx: &Context, player: usize) -> Direction
+    {
+        self.input(ctx.ticks).unwrap_or(ctx.snakes[player].direction)
+    }
+}
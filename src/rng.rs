@@ -0,0 +1,38 @@
+/// Small seeded random number generator (SplitMix64).
+/// Used wherever the same seed has to give the same result,
+/// in the browser and natively.
+#[derive(Clone, Debug)]
+pub struct Rng
+{
+    state: u64
+}
+
+impl Rng
+{
+    pub const fn new(seed: u64) -> Rng
+    {
+        Rng { state: seed }
+    }
+
+    pub fn next_u64(&mut self) -> u64
+    {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Returns a number in `0..n`.
+    pub fn below(&mut self, n: usize) -> usize
+    {
+        (self.next_u64() % n as u64) as usize
+    }
+
+    /// Returns true with the given probability.
+    pub fn chance(&mut self, probability: f64) -> bool
+    {
+        ((self.next_u64() >> 11) as f64 / (1u64 << 53) as f64) < probability
+    }
+}
@@ -0,0 +1,232 @@
+use std::collections::HashMap;
+
+use crate::Direction;
+use crate::game::{Context, Events, Snapshot, new_game, restore, snapshot, step, survivor};
+use crate::level::Level;
+use crate::net::{HASH_INTERVAL, Message, SyncCheck};
+
+/// How many ticks past the last confirmed one the game is allowed to guess.
+/// Any further and it waits for the other player, like lockstep does.
+pub const MAX_ROLLBACK: usize = 8;
+
+/// A two player game that never waits on the other player's input.
+///
+/// Local input is used right away. The other snake is guessed to keep
+/// doing what its last known input said, which is nearly always right.
+/// The game before every guessed tick is kept, so when an input turns out
+/// to have been guessed wrong the game is put back to before that tick
+/// and played forward again with the real input.
+#[derive(Debug)]
+pub struct Rollback
+{
+    /// Which of the two snakes is played on this side.
+    pub player: usize,
+    /// The game as it is now, guesses included.
+    pub ctx: Context,
+
+    /// The next tick to be simulated.
+    pub tick: usize,
+    /// Every input of the other player before this tick is known.
+    confirmed: usize,
+
+    /// The game before every tick from `confirmed` on.
+    snapshots: HashMap<usize, Snapshot>,
+
+    local: HashMap<usize, Option<Direction>>,
+    remote: HashMap<usize, Option<Direction>>,
+    /// What the other player's input was guessed to be, for the ticks it isn't known for yet.
+    predicted: HashMap<usize, Option<Direction>>,
+    /// The latest known input of the other player, used as the guess for the ticks after it.
+    latest_remote: Option<Direction>,
+
+    /// The tick the game ends on as simulated so far, with the winner.
+    /// Only final once the inputs up to that tick are confirmed.
+    ended: Option<(usize, Option<usize>)>,
+
+    outbox: Vec<Message>,
+    sync: SyncCheck,
+
+    /// How many times the game was put back to fix a wrong guess.
+    pub rollbacks: usize
+}
+
+impl Rollback
+{
+    pub fn new(player: usize, level: &Level, seed: u64) -> Rollback
+    {
+        Rollback {
+            player,
+            ctx: new_game(level, 2, seed),
+            tick: 0,
+            confirmed: 0,
+            snapshots: HashMap::new(),
+            local: HashMap::new(),
+            remote: HashMap::new(),
+            predicted: HashMap::new(),
+            latest_remote: None,
+            ended: None,
+            outbox: vec![],
+            sync: SyncCheck::default(),
+            rollbacks: 0
+        }
+    }
+
+    /// Whether the game is over for sure, with no guessed inputs left that could undo it.
+    pub fn finished(&self) -> bool
+    {
+        self.sync.finished()
+    }
+
+    /// The winner once the game is over for sure. No winner is a draw.
+    pub fn result(&self) -> Option<Option<usize>>
+    {
+        match self.finished() {
+            true  => self.ended.map(|(_, winner)| winner),
+            false => None
+        }
+    }
+
+    /// The first tick the two games were found to differ on.
+    pub fn desync(&self) -> Option<usize>
+    {
+        self.sync.desync
+    }
+
+    /// The messages for the other player since this was last called.
+    pub fn messages(&mut self) -> Vec<Message>
+    {
+        std::mem::take(&mut self.outbox)
+    }
+
+    /// Steps the game with `input` as this player's input for the next tick.
+    /// Returns nothing if the game has to wait for the other player first,
+    /// because it is too far ahead or ended on a guess.
+    pub fn advance(&mut self, input: Option<Direction>) -> Option<Events>
+    {
+        if self.ended.is_some() { return None }
+        if self.tick >= self.confirmed + MAX_ROLLBACK { return None }
+
+        // An input already sent for this tick can't be taken back.
+        if !self.local.contains_key(&self.tick) {
+            self.local.insert(self.tick, input);
+            self.outbox.push(Message::Input { tick: self.tick, player: self.player, direction: input });
+        }
+
+        let events = self.simulate(self.tick);
+        self.confirm();
+
+        Some(events)
+    }
+
+    /// Takes in a message from the other player.
+    /// Returns true if the game had to be put back because of it.
+    pub fn receive(&mut self, message: Message) -> bool
+    {
+        match message {
+            Message::Input { tick, player, direction } => {
+                if player == self.player || player > 1 || tick < self.confirmed { return false }
+
+                self.remote.insert(tick, direction);
+                self.latest_remote = direction;
+
+                let wrong = self.predicted.remove(&tick).is_some_and(|guess| guess != direction);
+                if wrong { self.rollback(tick); }
+
+                self.confirm();
+                wrong
+            }
+            Message::Hash { tick, hash } => {
+                self.sync.remote_hash(tick, hash);
+                false
+            }
+            Message::GameOver { tick, winner } => {
+                self.sync.remote_result(tick, winner);
+                false
+            }
+            Message::Join { .. } | Message::Rematch => false
+        }
+    }
+
+    /// Plays a single tick, guessing the other player's input if it isn't known yet.
+    fn simulate(&mut self, tick: usize) -> Events
+    {
+        self.snapshots.insert(tick, snapshot(&self.ctx));
+
+        let remote = match self.remote.get(&tick) {
+            Some(&input) => input,
+            None => {
+                self.predicted.insert(tick, self.latest_remote);
+                self.latest_remote
+            }
+        };
+
+        let mut inputs = [None; 2];
+        inputs[self.player]     = self.local.get(&tick).copied().flatten();
+        inputs[1 - self.player] = remote;
+
+        let events = step(&mut self.ctx, &inputs);
+        self.tick  = tick + 1;
+
+        if !events.died.is_empty() {
+            self.ended = Some((tick, survivor(&self.ctx, &events.died)));
+        }
+
+        events
+    }
+
+    /// Puts the game back to before `from` and plays it forward again
+    /// up to where it was, with whatever inputs are known now.
+    fn rollback(&mut self, from: usize)
+    {
+        let Some(before) = self.snapshots.get(&from) else { return };
+        restore(&mut self.ctx, before);
+
+        let end = self.tick;
+        self.ended = None;
+        self.rollbacks += 1;
+
+        for tick in from..end {
+            self.simulate(tick);
+            if self.ended.is_some() { break }
+        }
+
+        // A snake can now die earlier than it did before.
+        let end = self.tick;
+        self.snapshots.retain(|&tick, _| tick < end);
+        self.predicted.retain(|&tick, _| tick < end);
+    }
+
+    /// Moves past every tick the inputs of both players are known for.
+    /// Those can't change anymore, so that's where hashes are taken
+    /// and where the end of the game becomes final.
+    fn confirm(&mut self)
+    {
+        while self.confirmed < self.tick && self.remote.contains_key(&self.confirmed) {
+            let tick = self.confirmed;
+            self.confirmed += 1;
+
+            self.snapshots.remove(&tick);
+            self.local.remove(&tick);
+            self.remote.remove(&tick);
+            self.predicted.remove(&tick);
+
+            let ended = self.ended.filter(|(end, _)| *end == tick);
+
+            if let Some((_, winner)) = ended {
+                self.sync.result(tick, winner);
+                self.outbox.push(Message::GameOver { tick, winner });
+            }
+
+            if ended.is_some() || (tick + 1).is_multiple_of(HASH_INTERVAL) {
+                // The game after this tick is the one before the next.
+                let hash = match self.snapshots.get(&(tick + 1)) {
+                    Some(after) => after.hash(),
+                    None        => snapshot(&self.ctx).hash()
+                };
+
+                self.sync.hash(tick, hash);
+                self.outbox.push(Message::Hash { tick, hash });
+            }
+        }
+    }
+}
@@ -0,0 +1,395 @@
+use crate::{
+    APPLE_COLOUR, BACKGROUND_COLOUR, Direction, EDITOR_GRID_COLOUR, FLEEING_APPLE_COLOUR, GRID_BOX_HEIGHT,
+    GRID_BOX_WIDTH, OBSTACLE_COLOUR, PORTAL_COLOUR, SCENE_HEIGHT, SCENE_WIDTH, SECOND_SNAKE_COLOUR, SNAKE_COLOUR,
+    terrain_colour
+};
+use crate::editor::{Editor, Tile};
+use crate::game::{self, Apple, AppleKind, Cell, Context, Snake};
+use crate::level::Terrain;
+use crate::particles::Particles;
+use crate::text::{self, GLYPH_HEIGHT, GLYPH_WIDTH, SPACING};
+
+/// Builds the whole board: floor tiles, snakes, obstacles and the apple.
+pub fn game_vertices(ctx: &Context, progress: f32, positions: &mut Vec<f32>, colours: &mut Vec<f32>)
+{
+    // Floor tiles go first so the snake is drawn over them.
+    for y in 0..ctx.height {
+        for x in 0..ctx.width {
+            let Some(terrain) = game::terrain_at(ctx, (x, y)) else { continue };
+            terrain_vertices(terrain, x, y, positions, colours);
+        }
+    }
+
+    for &(one, two) in &ctx.portals {
+        for (x, y) in [one, two] {
+            let mut portal_vertices = cell_box(x, y);
+
+            colours.append(&mut PORTAL_COLOUR.repeat(portal_vertices.len() / 2));
+            positions.append(&mut portal_vertices);
+        }
+    }
+
+    for (player, snake) in ctx.snakes.iter().enumerate() {
+        snake_vertices(snake, progress, positions);
+
+        let colour = match player {
+            0 => SNAKE_COLOUR,
+            _ => SECOND_SNAKE_COLOUR
+        };
+        colours.append(&mut colour.repeat(positions.len() / 2 - colours.len() / 3));
+    }
+
+    for &(x, y) in &ctx.obstacles {
+        let mut obstacle_vertices = cell_box(x, y);
+
+        colours.append(&mut OBSTACLE_COLOUR.repeat(obstacle_vertices.len() / 2));
+        positions.append(&mut obstacle_vertices);
+    }
+
+    if let Some(apple) = ctx.apple {
+        let mut apple_vertices = cell_box(apple.position.0, apple.position.1);
+        let apple_colour = apple_colour(&apple, ctx.ticks);
+
+        colours.append(&mut apple_colour.repeat(apple_vertices.len() / 2));
+        positions.append(&mut apple_vertices);
+    }
+}
+
+/// Builds the particles, over everything else. They shrink and
+/// fade into the background as they get older.
+pub fn particle_vertices(particles: &Particles, positions: &mut Vec<f32>, colours: &mut Vec<f32>)
+{
+    for particle in &particles.particles {
+        let left = 1. - particle.age / particle.lifetime;
+        let size = particle.size * left;
+
+        let colour: [f32; 3] = std::array::from_fn(|c| {
+            BACKGROUND_COLOUR[c] + (particle.colour[c] - BACKGROUND_COLOUR[c]) * left
+        });
+
+        let (x, y) = particle.position;
+        let mut vertices = create_box(x - size / 2., y - size / 2., size, size);
+
+        colours.append(&mut colour.repeat(vertices.len() / 2));
+        positions.append(&mut vertices);
+    }
+}
+
+/// What the HUD shows besides the game itself.
+#[derive(Clone, Debug, Default)]
+pub struct Hud
+{
+    /// Seconds the game has been played for, pauses left out. Nothing when
+    /// it isn't known here, as for spectators.
+    pub played: Option<f64>,
+    /// The level of the campaign being played, counting from 1, and how many there are.
+    pub level: Option<(usize, usize)>,
+    pub paused: bool
+}
+
+/// Scene pixels to a pixel of the font.
+const HUD_SCALE: f32            = 4.;
+const HUD_MARGIN: f32           = 16.;
+const HUD_COLOUR: [f32; 3]      = [1., 1., 1.];
+const SHADOW_COLOUR: [f32; 3]   = [0., 0., 0.];
+
+/// Builds the HUD over the top of the board. One snake gets its score, length,
+/// speed and the time played, two get each their score in their own colour.
+/// A game that's paused halfway says so in the middle.
+pub fn hud_vertices(ctx: &Context, hud: &Hud, positions: &mut Vec<f32>, colours: &mut Vec<f32>)
+{
+    let y = SCENE_HEIGHT - HUD_MARGIN - GLYPH_HEIGHT as f32 * HUD_SCALE;
+
+    match ctx.snakes.len() {
+        1 => {
+            let snake = &ctx.snakes[0];
+            let score = match ctx.target {
+                Some(target) => format!("SCORE {}/{}", snake.score, target),
+                None         => format!("SCORE {}", snake.score)
+            };
+
+            let mut line = format!("{}  LENGTH {}  SPEED {}MS", score, snake.body.len(), ctx.tick_duration.round());
+            if let Some(played) = hud.played {
+                let seconds = played as usize;
+                line = format!("{}  {}:{:02}", line, seconds / 60, seconds % 60);
+            }
+            if let Some((level, levels)) = hud.level {
+                line = format!("LEVEL {}/{}  {}", level, levels, line);
+            }
+
+            shadowed_text(&line, (HUD_MARGIN, y), HUD_SCALE, HUD_COLOUR, positions, colours);
+        }
+        _ => {
+            let left  = format!("P1 {}", ctx.snakes[0].score);
+            let right = format!("P2 {}", ctx.snakes[1].score);
+            let right_x = SCENE_WIDTH - HUD_MARGIN - text::text_width(&right, HUD_SCALE);
+
+            shadowed_text(&left, (HUD_MARGIN, y), HUD_SCALE, SNAKE_COLOUR, positions, colours);
+            shadowed_text(&right, (right_x, y), HUD_SCALE, SECOND_SNAKE_COLOUR, positions, colours);
+        }
+    }
+
+    if hud.paused {
+        let scale = HUD_SCALE * 3.;
+        let x     = (SCENE_WIDTH - text::text_width("PAUSED", scale)) / 2.;
+        let y     = (SCENE_HEIGHT - GLYPH_HEIGHT as f32 * scale) / 2.;
+
+        shadowed_text("PAUSED", (x, y), scale, HUD_COLOUR, positions, colours);
+    }
+}
+
+/// Builds `text` in the pixel font with its bottom left corner at `x`, `y`,
+/// every pixel of the font `scale` scene pixels big.
+pub fn text_vertices
+(
+    text: &str,
+    (x, y): (f32, f32),
+    scale: f32,
+    colour: [f32; 3],
+    positions: &mut Vec<f32>,
+    colours: &mut Vec<f32>
+)
+{
+    for (i, c) in text.chars().enumerate() {
+        let left = x + (i * (GLYPH_WIDTH + SPACING)) as f32 * scale;
+
+        for (row, bits) in text::glyph(c).into_iter().enumerate() {
+            let bottom = y + (GLYPH_HEIGHT - 1 - row) as f32 * scale;
+            let lit    = |column: usize| bits & (1 << (GLYPH_WIDTH - 1 - column)) != 0;
+            let mut column = 0;
+
+            // Pixels lit next to each other in a row make a single box.
+            while column < GLYPH_WIDTH {
+                if !lit(column) {
+                    column += 1;
+                    continue
+                }
+
+                let start = column;
+                while column < GLYPH_WIDTH && lit(column) {
+                    column += 1;
+                }
+
+                let mut vertices = create_box(left + start as f32 * scale, bottom, (column - start) as f32 * scale, scale);
+                colours.append(&mut colour.repeat(vertices.len() / 2));
+                positions.append(&mut vertices);
+            }
+        }
+    }
+}
+
+/// Text with a shadow down and to the right, so it reads over anything on the board.
+fn shadowed_text
+(
+    text: &str,
+    at: (f32, f32),
+    scale: f32,
+    colour: [f32; 3],
+    positions: &mut Vec<f32>,
+    colours: &mut Vec<f32>
+)
+{
+    let (x, y) = at;
+    let offset = scale / 2.;
+
+    text_vertices(text, (x + offset, y - offset), scale, SHADOW_COLOUR, positions, colours);
+    text_vertices(text, (x, y), scale, colour, positions, colours);
+}
+
+/// Builds the editor view: every tile of the level, plus a preview
+/// of the brush under the mouse.
+pub(crate) fn editor_vertices(editor: &Editor, positions: &mut Vec<f32>, colours: &mut Vec<f32>)
+{
+    let tile_colour = |tile: Tile| match tile {
+        Tile::Empty            => EDITOR_GRID_COLOUR,
+        Tile::Wall             => OBSTACLE_COLOUR,
+        Tile::Portal           => PORTAL_COLOUR,
+        Tile::Start            => SNAKE_COLOUR,
+        Tile::Apple            => APPLE_COLOUR,
+        Tile::Terrain(terrain) => terrain_colour(terrain)
+    };
+
+    let mut push = |mut vertices: Vec<f32>, colour: [f32; 3]| {
+        colours.append(&mut colour.repeat(vertices.len() / 2));
+        positions.append(&mut vertices);
+    };
+
+    for y in 0..editor.height {
+        for x in 0..editor.width {
+            let tile = editor.tile(x, y);
+            let x = x as f32 * GRID_BOX_WIDTH;
+            let y = y as f32 * GRID_BOX_HEIGHT;
+
+            // Leave a thin gap between the tiles so the grid is visible.
+            push(create_box(x + 1., y + 1., GRID_BOX_WIDTH - 2., GRID_BOX_HEIGHT - 2.), tile_colour(tile));
+
+            // Marks which way the snake starts moving, or where the terrain pushes it.
+            let marker = match tile {
+                Tile::Start => Some(editor.direction),
+                Tile::Terrain(Terrain::Conveyor(direction) | Terrain::Gate(direction)) => Some(direction),
+                _ => None
+            };
+
+            if let Some(direction) = marker {
+                push(direction_marker(x, y, direction), BACKGROUND_COLOUR);
+            }
+        }
+    }
+
+    if let Some((x, y)) = editor.hover {
+        let preview = create_box
+        (
+            x as f32 * GRID_BOX_WIDTH + GRID_BOX_WIDTH / 4.,
+            y as f32 * GRID_BOX_HEIGHT + GRID_BOX_HEIGHT / 4.,
+            GRID_BOX_WIDTH / 2.,
+            GRID_BOX_HEIGHT / 2.
+        );
+
+        push(preview, tile_colour(editor.brush));
+    }
+}
+
+/// Builds a terrain tile, with a marker pointing the way
+/// conveyors push and gates let the snake through.
+fn terrain_vertices(terrain: Terrain, x: usize, y: usize, positions: &mut Vec<f32>, colours: &mut Vec<f32>)
+{
+    let mut vertices = cell_box(x, y);
+    colours.append(&mut terrain_colour(terrain).repeat(vertices.len() / 2));
+    positions.append(&mut vertices);
+
+    if let Terrain::Conveyor(direction) | Terrain::Gate(direction) = terrain {
+        let mut vertices = direction_marker(x as f32 * GRID_BOX_WIDTH, y as f32 * GRID_BOX_HEIGHT, direction);
+        colours.append(&mut BACKGROUND_COLOUR.repeat(vertices.len() / 2));
+        positions.append(&mut vertices);
+    }
+}
+
+/// A small box off the centre of the cell at `x`, `y`, on the `direction` side.
+fn direction_marker(x: f32, y: f32, direction: Direction) -> Vec<f32>
+{
+    let (offset_x, offset_y) = match direction {
+        Direction::Up    => (0., GRID_BOX_HEIGHT / 3.),
+        Direction::Down  => (0., -GRID_BOX_HEIGHT / 3.),
+        Direction::Left  => (-GRID_BOX_WIDTH / 3., 0.),
+        Direction::Right => (GRID_BOX_WIDTH / 3., 0.),
+    };
+
+    create_box
+    (
+        x + GRID_BOX_WIDTH / 3. + offset_x,
+        y + GRID_BOX_HEIGHT / 3. + offset_y,
+        GRID_BOX_WIDTH / 3.,
+        GRID_BOX_HEIGHT / 3.
+    )
+}
+
+/// Builds the snake for the current point of the step animation.
+fn snake_vertices(snake: &Snake, progress: f32, positions: &mut Vec<f32>)
+{
+    let len = snake.body.len();
+
+    // The body is drawn in place, so the turns stay filled while
+    // only the head and the tail move.
+    for &(x, y) in &snake.body[1..len - 1] {
+        positions.append(&mut cell_box(x, y));
+    }
+
+    segment_vertices(snake.previous[0], snake.body[0], snake.headings[0], progress, positions);
+    segment_vertices(snake.previous[len - 1], snake.body[len - 1], snake.headings[len - 1], progress, positions);
+}
+
+/// Builds a single part of the snake moving from one cell into another.
+///
+/// If the cells are not next to each other the part went through a portal
+/// or over the board edge, which are the same thing here. The part is then
+/// split in two: whatever has not gone in yet stays in the old cell,
+/// and whatever has come out is drawn in the new one.
+fn segment_vertices(from: Cell, to: Cell, heading: Direction, progress: f32, positions: &mut Vec<f32>)
+{
+    if from == to {
+        positions.append(&mut cell_box(to.0, to.1));
+        return
+    }
+
+    let (dx, dy) = heading.offset();
+
+    let from_x = from.0 as f32 * GRID_BOX_WIDTH;
+    let from_y = from.1 as f32 * GRID_BOX_HEIGHT;
+    let to_x   = to.0 as f32 * GRID_BOX_WIDTH;
+    let to_y   = to.1 as f32 * GRID_BOX_HEIGHT;
+
+    let adjacent = from.0 as isize + dx == to.0 as isize && from.1 as isize + dy == to.1 as isize;
+
+    if adjacent {
+        positions.append(&mut create_box
+        (
+            from_x + (to_x - from_x) * progress,
+            from_y + (to_y - from_y) * progress,
+            GRID_BOX_WIDTH,
+            GRID_BOX_HEIGHT
+        ));
+        return
+    }
+
+    let gone_width  = GRID_BOX_WIDTH * progress;
+    let gone_height = GRID_BOX_HEIGHT * progress;
+    let left_width  = GRID_BOX_WIDTH - gone_width;
+    let left_height = GRID_BOX_HEIGHT - gone_height;
+
+    let (mut leaving, mut entering) = match heading {
+        Direction::Right => (
+            create_box(from_x + gone_width, from_y, left_width, GRID_BOX_HEIGHT),
+            create_box(to_x, to_y, gone_width, GRID_BOX_HEIGHT)
+        ),
+        Direction::Left => (
+            create_box(from_x, from_y, left_width, GRID_BOX_HEIGHT),
+            create_box(to_x + left_width, to_y, gone_width, GRID_BOX_HEIGHT)
+        ),
+        Direction::Up => (
+            create_box(from_x, from_y + gone_height, GRID_BOX_WIDTH, left_height),
+            create_box(to_x, to_y, GRID_BOX_WIDTH, gone_height)
+        ),
+        Direction::Down => (
+            create_box(from_x, from_y, GRID_BOX_WIDTH, left_height),
+            create_box(to_x, to_y + left_height, GRID_BOX_WIDTH, gone_height)
+        ),
+    };
+
+    positions.append(&mut leaving);
+    positions.append(&mut entering);
+}
+
+/// Fades the apple into the background as its lifetime runs out.
+fn apple_colour(apple: &Apple, ticks: usize) -> [f32; 3]
+{
+    let colour = match apple.kind {
+        AppleKind::Still   => APPLE_COLOUR,
+        AppleKind::Fleeing => FLEEING_APPLE_COLOUR
+    };
+
+    let remaining = 0.25 + 0.75 * apple.remaining(ticks);
+
+    [
+        BACKGROUND_COLOUR[0] + (colour[0] - BACKGROUND_COLOUR[0]) * remaining,
+        BACKGROUND_COLOUR[1] + (colour[1] - BACKGROUND_COLOUR[1]) * remaining,
+        BACKGROUND_COLOUR[2] + (colour[2] - BACKGROUND_COLOUR[2]) * remaining,
+    ]
+}
+
+fn cell_box(x: usize, y: usize) -> Vec<f32>
+{
+    create_box(x as f32 * GRID_BOX_WIDTH, y as f32 * GRID_BOX_HEIGHT, GRID_BOX_WIDTH, GRID_BOX_HEIGHT)
+}
+
+fn create_box(x: f32, y: f32, width: f32, height: f32) -> Vec<f32>
+{
+    vec![
+        x,         y,
+        x + width, y,
+        x,         y + height,
+
+        x + width, y + height,
+        x,         y + height,
+        x + width, y
+    ]
+}
@@ -0,0 +1,316 @@
+use crate::Direction;
+use crate::game::{Apple, AppleKind, Cell, Context, Snake, new_game};
+use crate::level::{Level, parse_level};
+
+/// A keyframe goes out this often, so spectators joining late can catch up.
+pub const KEYFRAME_INTERVAL: usize = 100;
+
+/// How a snake changed over a tick.
+#[derive(PartialEq, Clone, Debug)]
+pub struct SnakeDelta
+{
+    /// New cells at the head, head first, with the direction they were entered in.
+    pub added: Vec<(Cell, Direction)>,
+    /// How many cells came off the tail.
+    pub removed: usize,
+    pub score: usize
+}
+
+/// What a spectator needs to follow the game for a tick.
+///
+/// Most frames only hold what changed since the frame before. Every now and
+/// then a keyframe holds the whole game instead, level included, with every
+/// snake written out as cells added to an empty one.
+///
+/// Frames are sent as text, a line of fields separated by spaces.
+/// Keyframes have the level after it, in the level format:
+///
+/// ```text
+/// delta 41 37 201.3 = 6,5,u/1/2 9,3,l//2
+/// key 100 96 190.2 4,4,s,90,- 6,6,u;6,5,u;6,4,r;5,4,r/0/3
+/// name: Open field
+/// ...
+/// ```
+///
+/// The fields are the frame number, the game tick, the tick duration, the apple
+/// (`=` if it didn't change, `-` if there is none) and then every snake.
+#[derive(Clone, Debug)]
+pub struct Frame
+{
+    pub sequence: usize,
+    pub ticks: usize,
+    pub tick_duration: f64,
+    /// Nothing if the apple didn't change.
+    pub apple: Option<Option<Apple>>,
+    pub snakes: Vec<SnakeDelta>,
+    /// Only keyframes have the level.
+    pub level: Option<Level>
+}
+
+impl Frame
+{
+    pub fn encode(&self) -> String
+    {
+        let apple = match self.apple {
+            None              => String::from("="),
+            Some(None)        => String::from("-"),
+            Some(Some(apple)) => format!
+            (
+                "{},{},{},{},{}",
+                apple.position.0,
+                apple.position.1,
+                match apple.kind { AppleKind::Still => 's', AppleKind::Fleeing => 'f' },
+                apple.spawned_at,
+                apple.lifetime.map_or(String::from("-"), |lifetime| lifetime.to_string())
+            )
+        };
+
+        let snakes: Vec<String> = self.snakes
+            .iter()
+            .map(|snake| {
+                let added: Vec<String> = snake.added
+                    .iter()
+                    .map(|((x, y), direction)| format!("{},{},{}", x, y, &direction.name()[..1]))
+                    .collect();
+
+                format!("{}/{}/{}", added.join(";"), snake.removed, snake.score)
+            })
+            .collect();
+
+        let kind = if self.level.is_some() { "key" } else { "delta" };
+        let mut text = format!("{} {} {} {} {} {}", kind, self.sequence, self.ticks, self.tick_duration, apple, snakes.join(" "));
+
+        if let Some(level) = &self.level {
+            text.push('\n');
+            text.push_str(&level.to_text());
+        }
+
+        text
+    }
+
+    /// Reads a frame back. Anything malformed gives nothing.
+    pub fn decode(text: &str) -> Option<Frame>
+    {
+        let (line, level) = match text.split_once('\n') {
+            Some((line, level)) => (line, Some(parse_level(level).ok()?)),
+            None                => (text, None)
+        };
+
+        let mut fields = line.split_whitespace();
+
+        match (fields.next()?, &level) {
+            ("key", Some(_)) | ("delta", None) => (),
+            _ => return None
+        }
+
+        let sequence      = fields.next()?.parse().ok()?;
+        let ticks         = fields.next()?.parse().ok()?;
+        let tick_duration = fields.next()?.parse().ok()?;
+
+        let apple = match fields.next()? {
+            "=" => None,
+            "-" => Some(None),
+            apple => {
+                let apple: Vec<&str> = apple.split(',').collect();
+                let [x, y, kind, spawned_at, lifetime] = apple[..] else { return None };
+
+                Some(Some(Apple {
+                    position: (x.parse().ok()?, y.parse().ok()?),
+                    kind: match kind {
+                        "s" => AppleKind::Still,
+                        "f" => AppleKind::Fleeing,
+                        _ => return None
+                    },
+                    spawned_at: spawned_at.parse().ok()?,
+                    lifetime: match lifetime {
+                        "-" => None,
+                        lifetime => Some(lifetime.parse().ok()?)
+                    }
+                }))
+            }
+        };
+
+        let snakes = fields
+            .map(|snake| {
+                let snake: Vec<&str> = snake.split('/').collect();
+                let [added, removed, score] = snake[..] else { return None };
+
+                let added = added
+                    .split(';')
+                    .filter(|cell| !cell.is_empty())
+                    .map(|cell| {
+                        let cell: Vec<&str> = cell.split(',').collect();
+                        let [x, y, direction] = cell[..] else { return None };
+
+                        let direction = match direction {
+                            "u" => Direction::Up,
+                            "d" => Direction::Down,
+                            "l" => Direction::Left,
+                            "r" => Direction::Right,
+                            _ => return None
+                        };
+
+                        Some(((x.parse().ok()?, y.parse().ok()?), direction))
+                    })
+                    .collect::<Option<Vec<_>>>()?;
+
+                Some(SnakeDelta { added, removed: removed.parse().ok()?, score: score.parse().ok()? })
+            })
+            .collect::<Option<Vec<_>>>()?;
+
+        Some(Frame { sequence, ticks, tick_duration, apple, snakes, level })
+    }
+}
+
+/// What changed between two ticks of the same game.
+fn snake_delta(before: &Snake, after: &Snake) -> SnakeDelta
+{
+    // New cells are at the head, followed by whatever is left of the old body.
+    // Taking every cell as new always fits, so there is always an answer.
+    let added = (0..=after.body.len())
+        .find(|&added| before.body.starts_with(&after.body[added..]))
+        .unwrap_or(after.body.len());
+
+    SnakeDelta {
+        added: after.body[..added].iter().copied().zip(after.headings[..added].iter().copied()).collect(),
+        removed: before.body.len() - (after.body.len() - added),
+        score: after.score
+    }
+}
+
+/// Applies what changed over a tick, keeping where the snakes were
+/// so the step can be animated like it is for the player.
+fn apply(ctx: &mut Context, frame: &Frame)
+{
+    ctx.ticks         = frame.ticks;
+    ctx.tick_duration = frame.tick_duration;
+
+    if let Some(apple) = frame.apple {
+        ctx.apple = apple;
+    }
+
+    for (snake, delta) in ctx.snakes.iter_mut().zip(&frame.snakes) {
+        snake.previous = snake.body.clone();
+
+        for &(cell, heading) in delta.added.iter().rev() {
+            snake.body.insert(0, cell);
+            snake.headings.insert(0, heading);
+        }
+
+        if let Some(&(_, heading)) = delta.added.first() {
+            snake.direction = heading;
+        }
+
+        let len = snake.body.len().saturating_sub(delta.removed);
+        snake.body.truncate(len);
+        snake.headings.truncate(len);
+
+        // A growing snake leaves its tail where it was for a step.
+        while snake.previous.len() < snake.body.len() {
+            let tail = snake.previous.last().copied().unwrap_or(snake.body[snake.body.len() - 1]);
+            snake.previous.push(tail);
+        }
+
+        snake.score = delta.score;
+    }
+}
+
+/// Turns the game played on this side into frames for spectators.
+#[derive(Debug, Default)]
+pub struct Broadcaster
+{
+    sequence: usize,
+    /// The game as of the last frame.
+    last: Option<Context>
+}
+
+impl Broadcaster
+{
+    /// Makes the next frame a keyframe, for when a new game starts.
+    pub fn reset(&mut self)
+    {
+        self.last = None;
+    }
+
+    pub fn frame(&mut self, ctx: &Context, level: &Level) -> Frame
+    {
+        // A new game always starts with a keyframe.
+        if self.last.is_none() { self.sequence = 0; }
+
+        let frame = match &self.last {
+            Some(last) if !self.sequence.is_multiple_of(KEYFRAME_INTERVAL) => Frame {
+                sequence: self.sequence,
+                ticks: ctx.ticks,
+                tick_duration: ctx.tick_duration,
+                apple: (last.apple != ctx.apple).then_some(ctx.apple),
+                snakes: last.snakes.iter().zip(&ctx.snakes).map(|(before, after)| snake_delta(before, after)).collect(),
+                level: None
+            },
+            _ => Frame {
+                sequence: self.sequence,
+                ticks: ctx.ticks,
+                tick_duration: ctx.tick_duration,
+                apple: Some(ctx.apple),
+                snakes: ctx.snakes
+                    .iter()
+                    .map(|snake| SnakeDelta {
+                        added: snake.body.iter().copied().zip(snake.headings.iter().copied()).collect(),
+                        removed: 0,
+                        score: snake.score
+                    })
+                    .collect(),
+                level: Some(level.clone())
+            }
+        };
+
+        self.sequence += 1;
+        self.last      = Some(ctx.clone());
+
+        frame
+    }
+}
+
+/// A read-only copy of someone else's game, built up from their frames.
+#[derive(Debug, Default)]
+pub struct Spectator
+{
+    pub ctx: Option<Context>,
+    /// The frame expected next. After a missed frame only a keyframe will do.
+    next: Option<usize>
+}
+
+impl Spectator
+{
+    /// Applies a frame. Returns false if it had to be skipped
+    /// because a frame before it went missing.
+    pub fn receive(&mut self, frame: &Frame) -> bool
+    {
+        match (&frame.level, self.ctx.as_mut()) {
+            (Some(level), _) => {
+                let mut ctx = new_game(level, frame.snakes.len(), 0);
+
+                for snake in &mut ctx.snakes {
+                    snake.body.clear();
+                    snake.headings.clear();
+                }
+
+                apply(&mut ctx, frame);
+
+                // Nothing moved yet.
+                for snake in &mut ctx.snakes {
+                    snake.previous = snake.body.clone();
+                }
+
+                self.ctx = Some(ctx);
+            }
+            (None, Some(ctx)) if self.next == Some(frame.sequence) => apply(ctx, frame),
+            _ => {
+                self.next = None;
+                return false
+            }
+        }
+
+        self.next = Some(frame.sequence + 1);
+        true
+    }
+}
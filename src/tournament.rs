@@ -0,0 +1,150 @@
+use std::fmt;
+
+use crate::bot::Strategy;
+use crate::game::{Death, new_game, step};
+use crate::level::Level;
+
+/// How a single game played by a strategy went.
+#[derive(Copy, Clone, Debug)]
+pub struct Run
+{
+    pub score: usize,
+    pub ticks: usize,
+    /// Nothing if the snake was still alive when the game ended.
+    pub death: Option<Death>
+}
+
+/// Lets a strategy play a whole game on its own, without a browser.
+///
+/// The game ends when the snake dies, the target of the level is reached,
+/// there's no room left for another apple, or after `max_ticks` ticks.
+pub fn play(strategy: &mut dyn Strategy, level: &Level, seed: u64, max_ticks: usize) -> Run
+{
+    let mut ctx = new_game(level, 1, seed);
+
+    for _ in 0..max_ticks {
+        let input  = strategy.next_move(&ctx, 0);
+        let events = step(&mut ctx, &[Some(input)]);
+
+        if let Some(&(_, death)) = events.died.first() {
+            return Run { score: ctx.snakes[0].score, ticks: ctx.ticks, death: Some(death) }
+        }
+
+        if ctx.target.is_some_and(|target| ctx.snakes[0].score >= target) || ctx.apple.is_none() { break }
+    }
+
+    Run { score: ctx.snakes[0].score, ticks: ctx.ticks, death: None }
+}
+
+/// How a strategy did over every game it played.
+#[derive(Clone, Debug)]
+pub struct Report
+{
+    pub strategy: String,
+    pub games: usize,
+
+    pub mean_score: f64,
+    pub median_score: f64,
+
+    /// How many ticks the snake lasted.
+    pub mean_ticks: f64,
+    pub median_ticks: f64,
+
+    /// How many games ended without the snake dying.
+    pub survived: usize,
+    /// How many games ended in every kind of death, most common first.
+    pub deaths: Vec<(Death, usize)>
+}
+
+impl Report
+{
+    pub fn new(strategy: String, runs: &[Run]) -> Report
+    {
+        let scores: Vec<usize> = runs.iter().map(|run| run.score).collect();
+        let ticks: Vec<usize>  = runs.iter().map(|run| run.ticks).collect();
+
+        let mut deaths: Vec<(Death, usize)> = vec![];
+
+        for death in runs.iter().filter_map(|run| run.death) {
+            match deaths.iter_mut().find(|(counted, _)| *counted == death) {
+                Some((_, count)) => *count += 1,
+                None             => deaths.push((death, 1))
+            }
+        }
+
+        deaths.sort_by_key(|&(_, count)| std::cmp::Reverse(count));
+
+        Report {
+            strategy,
+            games: runs.len(),
+            mean_score: mean(&scores),
+            median_score: median(&scores),
+            mean_ticks: mean(&ticks),
+            median_ticks: median(&ticks),
+            survived: runs.iter().filter(|run| run.death.is_none()).count(),
+            deaths
+        }
+    }
+}
+
+impl fmt::Display for Report
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+    {
+        let deaths: Vec<String> = self.deaths
+            .iter()
+            .map(|(death, count)| format!("{:?} {}", death, count))
+            .collect();
+
+        write!
+        (
+            f,
+            "{:<12} score {:>7.1} mean {:>6.1} median   ticks {:>8.1} mean {:>7.1} median   survived {}/{}   {}",
+            self.strategy,
+            self.mean_score,
+            self.median_score,
+            self.mean_ticks,
+            self.median_ticks,
+            self.survived,
+            self.games,
+            deaths.join(", ")
+        )
+    }
+}
+
+/// Plays `games` games of the level with every strategy and reports how each did.
+/// Game `n` is played with seed `n`, so every strategy gets the same apples
+/// for as long as they play the same way, and every run can be played again.
+pub fn tournament(strategies: &mut [Box<dyn Strategy>], level: &Level, games: usize, max_ticks: usize) -> Vec<Report>
+{
+    strategies
+        .iter_mut()
+        .map(|strategy| {
+            let runs: Vec<Run> = (0..games as u64)
+                .map(|seed| play(strategy.as_mut(), level, seed, max_ticks))
+                .collect();
+
+            Report::new(strategy.name(), &runs)
+        })
+        .collect()
+}
+
+fn mean(values: &[usize]) -> f64
+{
+    match values.len() {
+        0 => 0.,
+        n => values.iter().sum::<usize>() as f64 / n as f64
+    }
+}
+
+fn median(values: &[usize]) -> f64
+{
+    let mut sorted = values.to_vec();
+    sorted.sort_unstable();
+
+    match sorted.len() {
+        0 => 0.,
+        n if n % 2 == 0 => (sorted[n / 2 - 1] + sorted[n / 2]) as f64 / 2.,
+        n => sorted[n / 2] as f64
+    }
+}
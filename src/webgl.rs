@@ -0,0 +1,433 @@
+use wasm_bindgen::prelude::*;
+use web_sys::{
+    HtmlCanvasElement, WebGl2RenderingContext, WebGlBuffer, WebGlProgram, WebGlShader, WebGlVertexArrayObject
+};
+
+use crate::{BACKGROUND_COLOUR, SCENE_HEIGHT, SCENE_WIDTH};
+use crate::effects::PostProcess;
+use crate::render::{Effect, Renderer, ShaderError, letterbox};
+
+/// Draws the board with WebGL 2, on the GPU.
+///
+/// The browser can take the context away at any time, along with the
+/// program and buffers made with it. Until it gives the context back
+/// nothing is drawn, and then `restore` makes them again.
+pub struct WebGl
+{
+    canvas: HtmlCanvasElement,
+    context: WebGl2RenderingContext,
+    program: WebGlProgram,
+    buffers: Buffers,
+
+    /// The part of the canvas the scene is drawn on, see `letterbox`.
+    viewport: [i32; 4],
+    effects: Vec<Effect>,
+    /// Only made once there are effects to draw.
+    post: Option<PostProcess>,
+    /// When it started drawing, for the `time` of the effects.
+    started: f64,
+    /// Shaders loaded from outside, if they're used instead of the built-in ones.
+    /// Kept to make them again after the context is lost.
+    shaders: Option<(String, String)>
+}
+
+/// Where the vertices are uploaded to every frame.
+struct Buffers
+{
+    vao: WebGlVertexArrayObject,
+    positions: WebGlBuffer,
+    colours: WebGlBuffer
+}
+
+impl WebGl
+{
+    pub fn new(canvas: &HtmlCanvasElement) -> Result<WebGl, JsValue>
+    {
+        let context = canvas
+            .get_context("webgl2")?
+            .ok_or_else(|| JsValue::from_str("WebGL 2 isn't available"))?
+            .dyn_into::<WebGl2RenderingContext>()?;
+
+        let program = create_program(&context, VERTEX_SHADER, FRAGMENT_SHADER)
+            .map_err(|error| JsValue::from_str(&error.to_string()))?;
+
+        let buffers  = create_buffers(&context, &program)?;
+        let viewport = fit(&context, &program, canvas.width(), canvas.height());
+
+        Ok(WebGl {
+            canvas: canvas.clone(),
+            context,
+            program,
+            buffers,
+            viewport,
+            effects: vec![],
+            post: None,
+            started: js_sys::Date::now(),
+            shaders: None
+        })
+    }
+
+    /// Draws with `program` from now on, and lets go of the one before.
+    fn switch_program(&mut self, program: WebGlProgram) -> Result<(), String>
+    {
+        let buffers = match create_buffers(&self.context, &program) {
+            Ok(buffers) => buffers,
+            Err(error)  => {
+                self.context.delete_program(Some(&program));
+                return Err(error)
+            }
+        };
+
+        self.viewport = fit(&self.context, &program, self.canvas.width(), self.canvas.height());
+
+        std::mem::replace(&mut self.buffers, buffers).delete(&self.context);
+        self.context.delete_program(Some(&std::mem::replace(&mut self.program, program)));
+
+        Ok(())
+    }
+
+    fn builtin_program(&mut self) -> Result<(), String>
+    {
+        self.shaders = None;
+
+        let program = create_program(&self.context, VERTEX_SHADER, FRAGMENT_SHADER).map_err(|error| error.to_string())?;
+        self.switch_program(program)
+    }
+
+    /// Makes the textures and programs of the effects, if there are any to draw.
+    fn create_post_process(&mut self) -> Result<(), String>
+    {
+        if let Some(post) = self.post.take() {
+            post.delete(&self.context);
+        }
+
+        if self.effects.is_empty() { return Ok(()) }
+
+        let mut post = PostProcess::new(&self.context)?;
+        if let Err(error) = post.resize(&self.context, self.viewport[2], self.viewport[3]) {
+            post.delete(&self.context);
+            return Err(error)
+        }
+        self.post = Some(post);
+
+        Ok(())
+    }
+}
+
+impl Renderer for WebGl
+{
+    fn backend(&self) -> &'static str
+    {
+        "webgl2"
+    }
+
+    fn set_effects(&mut self, effects: &[Effect]) -> Result<(), String>
+    {
+        if self.effects == effects { return Ok(()) }
+
+        self.effects = effects.to_vec();
+        if self.post.is_some() && !effects.is_empty() { return Ok(()) }
+
+        self.create_post_process()
+    }
+
+    fn resize(&mut self, width: u32, height: u32)
+    {
+        self.viewport = fit(&self.context, &self.program, width, height);
+
+        if self.effects.is_empty() { return }
+
+        let resized = match self.post.as_mut() {
+            Some(post) => post.resize(&self.context, self.viewport[2], self.viewport[3]),
+            None       => self.create_post_process()
+        };
+
+        // Without the textures the effects can't be drawn, so the scene goes
+        // straight to the screen until a later resize manages to make them.
+        if resized.is_err() {
+            if let Some(post) = self.post.take() { post.delete(&self.context); }
+        }
+    }
+
+    fn restore(&mut self) -> Result<(), String>
+    {
+        let loaded = self.shaders
+            .as_ref()
+            .and_then(|(vertex, fragment)| create_program(&self.context, vertex, fragment).ok());
+
+        match loaded {
+            Some(program) => self.switch_program(program)?,
+            None          => self.builtin_program()?
+        }
+
+        self.create_post_process()
+    }
+
+    fn load_shaders(&mut self, vertex: &str, fragment: &str) -> Result<(), ShaderError>
+    {
+        let error = match create_program(&self.context, vertex, fragment) {
+            Ok(program) => {
+                self.shaders = Some((String::from(vertex), String::from(fragment)));
+                return self.switch_program(program).map_err(|error| ShaderError::new("renderer", error))
+            }
+            Err(error) => error
+        };
+
+        // Whatever was loaded before is let go as well.
+        if self.shaders.is_some() {
+            self.builtin_program().map_err(|error| ShaderError::new("renderer", error))?;
+        }
+
+        Err(error)
+    }
+
+    fn render(&mut self, positions: &[f32], colours: &[f32]) -> Result<(), String>
+    {
+        // Nothing can be drawn until the context is back.
+        if self.context.is_context_lost() { return Ok(()) }
+
+        let context = &self.context;
+        let vertices_count = (positions.len() / 2) as i32;
+
+        let time = ((js_sys::Date::now() - self.started) / 1000.) as f32;
+
+        // The effects leave their own program in use.
+        context.use_program(Some(&self.program));
+
+        // Only loaded shaders have it, for skins that move.
+        context.uniform1f(context.get_uniform_location(&self.program, "time").as_ref(), time);
+
+        // Outside of the scene the page shows through.
+        context.bind_framebuffer(WebGl2RenderingContext::FRAMEBUFFER, None);
+        context.disable(WebGl2RenderingContext::SCISSOR_TEST);
+        context.clear_color(0., 0., 0., 0.);
+        context.clear(WebGl2RenderingContext::COLOR_BUFFER_BIT);
+
+        // With effects on, the scene goes into a texture and they put it on the screen.
+        match self.post.as_ref() {
+            Some(post) => post.begin(context),
+            None => {
+                let [x, y, width, height] = self.viewport;
+                context.viewport(x, y, width, height);
+                context.enable(WebGl2RenderingContext::SCISSOR_TEST);
+            }
+        }
+
+        // Background colour.
+        context.clear_color(BACKGROUND_COLOUR[0], BACKGROUND_COLOUR[1], BACKGROUND_COLOUR[2], 1.0);
+        context.clear(WebGl2RenderingContext::COLOR_BUFFER_BIT);
+
+        draw_vertices(context, &self.buffers, positions, colours);
+
+        context.draw_arrays(WebGl2RenderingContext::TRIANGLES, 0, vertices_count);
+
+        if let Some(post) = self.post.as_ref() {
+            post.finish(context, &self.effects, self.viewport, time);
+        }
+
+        Ok(())
+    }
+}
+
+/// Compiles the shaders that draw the scene, and checks they take
+/// the vertices and the size of the scene the way they're given.
+fn create_program(context: &WebGl2RenderingContext, vertex: &str, fragment: &str) -> Result<WebGlProgram, ShaderError>
+{
+    let vertex_shader = compile_shader(context, WebGl2RenderingContext::VERTEX_SHADER, vertex)
+        .map_err(|log| ShaderError::from_log("vertex", &log))?;
+
+    let fragment_shader = match compile_shader(context, WebGl2RenderingContext::FRAGMENT_SHADER, fragment) {
+        Ok(shader) => shader,
+        Err(log)   => {
+            context.delete_shader(Some(&vertex_shader));
+            return Err(ShaderError::from_log("fragment", &log))
+        }
+    };
+
+    // The program keeps what it needs of the shaders once it's linked.
+    let linked = link_program(context, &vertex_shader, &fragment_shader);
+    context.delete_shader(Some(&vertex_shader));
+    context.delete_shader(Some(&fragment_shader));
+
+    let program = linked.map_err(|log| ShaderError::from_log("link", &log))?;
+
+    // Attributes and uniforms the shaders don't use are compiled away, so they're missing too.
+    let missing = ["position", "vertexColour"]
+        .into_iter()
+        .find(|attribute| context.get_attrib_location(&program, attribute) < 0);
+
+    let error = match missing {
+        Some(attribute) => ShaderError::new("interface", format!("The vertex shader doesn't use the `{}` attribute", attribute)),
+        None if context.get_uniform_location(&program, "resolution").is_none() => {
+            ShaderError::new("interface", "The vertex shader doesn't use the `resolution` uniform")
+        }
+        None => return Ok(program)
+    };
+
+    context.delete_program(Some(&program));
+    Err(error)
+}
+
+/// Points the drawing at the part of a `width` by `height` canvas the scene fits in, see `letterbox`.
+/// Gives back that part, as a viewport.
+fn fit(context: &WebGl2RenderingContext, program: &WebGlProgram, width: u32, height: u32) -> [i32; 4]
+{
+    let (x, y, width, height) = letterbox(width as f64, height as f64);
+    let [x, y, width, height] = [x, y, width, height].map(|value| value.round() as i32);
+
+    context.viewport(x, y, width, height);
+    context.scissor(x, y, width, height);
+
+    // Positions are in the scene, whatever size it's shown at.
+    context.use_program(Some(program));
+    let resolution_location = context.get_uniform_location(program, "resolution");
+    context.uniform2f(resolution_location.as_ref(), SCENE_WIDTH, SCENE_HEIGHT);
+
+    [x, y, width, height]
+}
+
+impl Buffers
+{
+    fn delete(&self, context: &WebGl2RenderingContext)
+    {
+        context.delete_vertex_array(Some(&self.vao));
+        context.delete_buffer(Some(&self.positions));
+        context.delete_buffer(Some(&self.colours));
+    }
+}
+
+/// Makes the buffers for positions and colours, and hooks them up to the program.
+fn create_buffers(context: &WebGl2RenderingContext, program: &WebGlProgram) -> Result<Buffers, String>
+{
+    let vao = context.create_vertex_array().ok_or("Failed to create vertex array object")?;
+    context.bind_vertex_array(Some(&vao));
+
+    let attribute = |name: &str, size: i32| -> Result<WebGlBuffer, String> {
+        let buffer = context.create_buffer().ok_or("Failed to create buffer")?;
+        context.bind_buffer(WebGl2RenderingContext::ARRAY_BUFFER, Some(&buffer));
+
+        let attrib_location = context.get_attrib_location(program, name) as u32;
+        context.vertex_attrib_pointer_with_i32
+        (
+            attrib_location, size, WebGl2RenderingContext::FLOAT, false, 0, 0
+        );
+        context.enable_vertex_attrib_array(attrib_location);
+
+        Ok(buffer)
+    };
+
+    let positions = attribute("position", 2)?;
+    let colours   = attribute("vertexColour", 3)?;
+
+    Ok(Buffers { vao, positions, colours })
+}
+
+fn draw_vertices(context: &WebGl2RenderingContext, buffers: &Buffers, vertices: &[f32], colours: &[f32])
+{
+    context.bind_vertex_array(Some(&buffers.vao));
+
+    for (buffer, data) in [(&buffers.positions, vertices), (&buffers.colours, colours)] {
+        context.bind_buffer(WebGl2RenderingContext::ARRAY_BUFFER, Some(buffer));
+
+        let array_buf = js_sys::Float32Array::new_with_length(data.len() as u32);
+        array_buf.copy_from(data);
+
+        context.buffer_data_with_array_buffer_view
+        (
+            WebGl2RenderingContext::ARRAY_BUFFER,
+            &array_buf,
+            WebGl2RenderingContext::DYNAMIC_DRAW,
+        );
+    }
+}
+
+pub fn compile_shader
+(
+    context: &WebGl2RenderingContext,
+    shader_type: u32,
+    source: &str
+) -> Result<WebGlShader, String>
+{
+    let shader = context
+        .create_shader(shader_type)
+        .ok_or_else(|| String::from("Unable to create shader object"))?;
+
+    context.shader_source(&shader, source);
+    context.compile_shader(&shader);
+
+    let compiled = context
+        .get_shader_parameter(&shader, WebGl2RenderingContext::COMPILE_STATUS)
+        .as_bool()
+        .unwrap_or(false);
+
+    if compiled { return Ok(shader) }
+
+    let error = context
+        .get_shader_info_log(&shader)
+        .unwrap_or_else(|| String::from("Unknown error creating shader"));
+
+    context.delete_shader(Some(&shader));
+    Err(error)
+}
+
+pub fn link_program
+(
+    context: &WebGl2RenderingContext,
+    vertex_shader: &WebGlShader,
+    fragment_shader: &WebGlShader,
+) -> Result<WebGlProgram, String>
+{
+    let program = context
+        .create_program()
+        .ok_or_else(|| String::from("Unable to create program"))?;
+
+    context.attach_shader(&program, vertex_shader);
+    context.attach_shader(&program, fragment_shader);
+
+    context.link_program(&program);
+
+    let program_linked = context
+        .get_program_parameter(&program, WebGl2RenderingContext::LINK_STATUS)
+        .as_bool()
+        .unwrap_or(false);
+
+    if program_linked { return Ok(program) }
+
+    let error = context
+        .get_program_info_log(&program)
+        .unwrap_or_else(|| String::from("Unknown error creating program object"));
+
+    context.delete_program(Some(&program));
+    Err(error)
+}
+
+const VERTEX_SHADER: &str = r##"#version 300 es
+
+uniform vec2 resolution;
+
+in vec2 position;
+
+in vec3 vertexColour;
+out vec3 fragmentColour;
+
+void main() {
+    fragmentColour = vertexColour;
+
+    vec2 zeroToOne = position / resolution;
+    vec2 zeroToTwo = zeroToOne * 2.0;
+    vec2 clipSpace = zeroToTwo - 1.0;
+    gl_Position = vec4(clipSpace, 0, 1);
+}
+"##;
+
+const FRAGMENT_SHADER: &str = r##"#version 300 es
+
+precision highp float;
+
+in vec3 fragmentColour;
+out vec4 outColour;
+
+void main() {
+    outColour = vec4(fragmentColour, 1.0);
+}
+"##;
@@ -0,0 +1,41 @@
+use web_rust::env::{Environment, ObservationKind, Rewards};
+use web_rust::level::builtin_levels;
+
+#[test]
+fn same_seed_same_game()
+{
+    let level = &builtin_levels()[0];
+
+    let mut one = Environment::new(level, ObservationKind::Grid, Rewards::default());
+    let mut two = Environment::new(level, ObservationKind::Grid, Rewards::default());
+
+    assert_eq!(one.reset(3), two.reset(3));
+    assert_eq!(one.reset(3).len(), one.shape().iter().product::<usize>());
+
+    for _ in 0..50 {
+        assert_eq!(one.step(None).observation, two.step(None).observation);
+    }
+}
+
+#[test]
+fn dying_ends_the_game()
+{
+    // The portals level has walls, and nothing between the start and the left one.
+    let level   = builtin_levels().into_iter().find(|level| level.name == "Portals").unwrap();
+    let rewards = Rewards::new(0., -1., 0., 0.);
+
+    let mut env = Environment::new(&level, ObservationKind::Features, rewards);
+    env.reset(0);
+
+    let steps: Vec<_> = (0..=level.start.0).map(|_| env.step(None)).collect();
+    let last = steps.last().unwrap();
+
+    assert!(steps[..steps.len() - 1].iter().all(|step| !step.done && step.reward == 0.));
+    assert!(last.done);
+    assert_eq!(last.reward, -1.);
+
+    // Whatever comes after changes nothing.
+    let after = env.step(None);
+    assert!(after.done);
+    assert_eq!(after.reward, 0.);
+}
@@ -0,0 +1,179 @@
+use web_rust::Direction;
+use web_rust::game::{Context, Death, new_game, step, step_duration, terrain_at};
+use web_rust::generator::{Layout, generate_level};
+use web_rust::level::{builtin_levels, parse_level};
+
+/// A game on a small board with the snake going right, and no apple to get in the way.
+fn game(grid: &str) -> Context
+{
+    let level   = parse_level(&format!("direction: right\n\n{}", grid)).unwrap();
+    let mut ctx = new_game(&level, 1, 0);
+    ctx.apple   = None;
+    ctx
+}
+
+#[test]
+fn ice_takes_the_steering()
+{
+    let mut ctx = game("..........\n....S~....\n..........\n");
+
+    step(&mut ctx, &[None]);
+    assert_eq!(ctx.snakes[0].body[0], (5, 1));
+
+    // Sliding on, no matter the input...
+    step(&mut ctx, &[Some(Direction::Up)]);
+    assert_eq!(ctx.snakes[0].body[0], (6, 1));
+
+    // ...until the head is off the ice.
+    step(&mut ctx, &[Some(Direction::Up)]);
+    assert_eq!(ctx.snakes[0].body[0], (6, 2));
+}
+
+#[test]
+fn mud_slows_the_step_down()
+{
+    let mut ctx = game("..........\n....S%....\n..........\n");
+    assert_eq!(step_duration(&ctx), ctx.tick_duration);
+
+    step(&mut ctx, &[None]);
+    assert_eq!(step_duration(&ctx), ctx.tick_duration * 2.);
+
+    step(&mut ctx, &[None]);
+    assert_eq!(step_duration(&ctx), ctx.tick_duration);
+}
+
+#[test]
+fn conveyors_push_the_snake()
+{
+    let mut ctx = game("..........\n....S^....\n..........\n");
+
+    step(&mut ctx, &[None]);
+    step(&mut ctx, &[Some(Direction::Down)]);
+
+    assert_eq!(ctx.snakes[0].body[0], (5, 2));
+    assert_eq!(ctx.snakes[0].direction, Direction::Up);
+}
+
+#[test]
+fn conveyors_are_walls_against_the_belt()
+{
+    let mut ctx = game("..........\n....S<....\n..........\n");
+
+    assert_eq!(step(&mut ctx, &[None]).died, vec![(0, Death::Obstacle)]);
+}
+
+#[test]
+fn gates_only_open_one_way()
+{
+    let mut ctx = game("..........\n....Se....\n..........\n");
+    assert!(step(&mut ctx, &[None]).died.is_empty());
+    assert_eq!(ctx.snakes[0].body[0], (5, 1));
+
+    let mut ctx = game("..........\n....Sw....\n..........\n");
+    assert_eq!(step(&mut ctx, &[None]).died, vec![(0, Death::Obstacle)]);
+
+    // From the side, too.
+    let mut ctx = game("..........\n....Sn....\n..........\n");
+    assert_eq!(step(&mut ctx, &[None]).died, vec![(0, Death::Obstacle)]);
+}
+
+#[test]
+fn portals_teleport_the_head()
+{
+    let mut ctx = game("..P.......\n....SP....\n..........\n");
+
+    step(&mut ctx, &[None]);
+    assert_eq!(ctx.snakes[0].body[0], (2, 2));
+
+    // And the snake keeps going the way it was.
+    step(&mut ctx, &[None]);
+    assert_eq!(ctx.snakes[0].body[0], (3, 2));
+}
+
+/// Two snakes on an empty board, to be put wherever the test needs them.
+fn versus(width: usize, height: usize) -> Context
+{
+    let mut grid = vec!['.'; width * height];
+    grid[width + 1] = 'S';
+
+    let rows: Vec<String> = grid.chunks(width).map(|row| row.iter().collect()).collect();
+    let level = parse_level(&format!("direction: right\n\n{}\n", rows.join("\n"))).unwrap();
+
+    let mut ctx = new_game(&level, 2, 0);
+    ctx.apple   = None;
+    ctx
+}
+
+fn place(ctx: &mut Context, player: usize, body: &[(usize, usize)], direction: Direction)
+{
+    let snake       = &mut ctx.snakes[player];
+    snake.body      = body.to_vec();
+    snake.previous  = body.to_vec();
+    snake.headings  = vec![direction; body.len()];
+    snake.direction = direction;
+}
+
+#[test]
+fn second_snake_mirrors_the_first_on_an_open_board()
+{
+    let ctx = new_game(&builtin_levels()[0], 2, 0);
+    let (one, two) = (&ctx.snakes[0], &ctx.snakes[1]);
+
+    assert_eq!(two.body[0], (ctx.width - 1 - one.body[0].0, ctx.height - 1 - one.body[0].1));
+    assert_eq!(two.direction, one.direction.opposite());
+}
+
+#[test]
+fn second_snake_starts_clear_of_everything()
+{
+    let layouts = ["maze", "rooms", "pillars", "arena"].map(|name| Layout::from_name(name).unwrap());
+
+    let generated = layouts.into_iter().flat_map(|layout| (0..200).map(move |seed| generate_level(layout, 16, 10, seed)));
+
+    for level in builtin_levels().into_iter().chain(generated) {
+        let mut ctx = new_game(&level, 2, 0);
+        let second  = ctx.snakes[1].clone();
+
+        for cell in &second.body {
+            assert!(!ctx.obstacles.contains(cell), "{}: second snake in a wall at {:?}", level.name, cell);
+            assert!(!ctx.snakes[0].body.contains(cell), "{}: snakes overlap at {:?}", level.name, cell);
+            assert!(terrain_at(&ctx, *cell).is_none(), "{}: second snake on terrain at {:?}", level.name, cell);
+            assert!(ctx.portals.iter().all(|&(one, two)| *cell != one && *cell != two), "{}: second snake on a portal", level.name);
+        }
+
+        // And it has room to get going.
+        ctx.apple = None;
+        let events = step(&mut ctx, &[None, None]);
+        assert!(events.died.iter().all(|&(player, _)| player != 1), "{}: second snake died at once", level.name);
+    }
+}
+
+#[test]
+fn meeting_head_on_kills_both()
+{
+    let mut ctx = versus(10, 3);
+    place(&mut ctx, 0, &[(4, 1), (3, 1), (2, 1), (1, 1)], Direction::Right);
+    place(&mut ctx, 1, &[(6, 1), (7, 1), (8, 1), (9, 1)], Direction::Left);
+
+    assert_eq!(step(&mut ctx, &[None, None]).died, vec![(0, Death::HeadOn), (1, Death::HeadOn)]);
+}
+
+#[test]
+fn running_into_itself_kills_the_snake()
+{
+    let mut ctx = versus(10, 5);
+    place(&mut ctx, 0, &[(2, 2), (3, 2), (3, 1), (2, 1), (1, 1), (0, 1)], Direction::Left);
+    place(&mut ctx, 1, &[(8, 4), (9, 4), (0, 4), (1, 4)], Direction::Left);
+
+    assert_eq!(step(&mut ctx, &[Some(Direction::Down), None]).died, vec![(0, Death::Body)]);
+}
+
+#[test]
+fn running_into_another_snake_only_kills_the_one_running()
+{
+    let mut ctx = versus(10, 5);
+    place(&mut ctx, 0, &[(4, 2), (3, 2), (2, 2), (1, 2)], Direction::Right);
+    place(&mut ctx, 1, &[(5, 4), (5, 3), (5, 2), (5, 1)], Direction::Up);
+
+    assert_eq!(step(&mut ctx, &[None, None]).died, vec![(0, Death::Snake)]);
+}
@@ -0,0 +1,80 @@
+use web_rust::game::new_game;
+use web_rust::generator::{Layout, fully_reachable, generate_level};
+use web_rust::level::parse_level;
+
+const LAYOUTS: [&str; 4] = ["maze", "rooms", "pillars", "arena"];
+
+fn layouts() -> impl Iterator<Item = Layout>
+{
+    LAYOUTS.iter().map(|name| Layout::from_name(name).unwrap())
+}
+
+#[test]
+fn same_seed_same_level()
+{
+    for layout in layouts() {
+        let one = generate_level(layout, 16, 10, 42);
+        let two = generate_level(layout, 16, 10, 42);
+
+        assert_eq!(one.to_text(), two.to_text());
+        assert_ne!(one.to_text(), generate_level(layout, 16, 10, 43).to_text());
+    }
+}
+
+#[test]
+fn every_free_cell_is_reachable()
+{
+    for layout in layouts() {
+        for seed in 0..200 {
+            let level = generate_level(layout, 16, 10, seed);
+            assert!(fully_reachable(&level), "{} has cells the snake can't get to", level.name);
+        }
+    }
+}
+
+#[test]
+fn the_start_is_clear()
+{
+    for layout in layouts() {
+        for seed in 0..200 {
+            let level = generate_level(layout, 16, 10, seed);
+            let snake = &new_game(&level, 1, seed).snakes[0];
+
+            // The snake starts going left, with three free cells in front of it.
+            let (x, y) = snake.body[0];
+            let ahead  = (1..=3).map(|i| ((x + level.width - i) % level.width, y));
+
+            for cell in snake.body.iter().copied().chain(ahead) {
+                assert!(!level.obstacles.contains(&cell), "{} has a wall at {:?}", level.name, cell);
+            }
+        }
+    }
+}
+
+#[test]
+fn tiny_boards_are_made_big_enough()
+{
+    for layout in layouts() {
+        for (width, height) in [(0, 0), (1, 1), (2, 10), (16, 0)] {
+            let level = generate_level(layout, width, height, 3);
+            assert!(level.width >= width && level.height >= height && fully_reachable(&level));
+        }
+    }
+}
+
+#[test]
+fn reachability_moves_like_the_snake()
+{
+    // The left two columns are only reachable around the edge...
+    let walled = "edges: walls\ndirection: left\n\n..#.....\n..#S....\n";
+    assert!(!fully_reachable(&parse_level(walled).unwrap()));
+    assert!(fully_reachable(&parse_level(&walled.replace("walls", "wrap")).unwrap()));
+
+    // ...or through a portal.
+    assert!(fully_reachable(&parse_level("edges: walls\ndirection: left\n\n..#.....\nP.#S...P\n").unwrap()));
+
+    // A gate only lets the snake through one way.
+    let gated = "edges: walls\ndirection: left\n\n..#.....\n..wS....\n";
+    assert!(fully_reachable(&parse_level(gated).unwrap()));
+    assert!(!fully_reachable(&parse_level(&gated.replace("wS", "eS")).unwrap()));
+}
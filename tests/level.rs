@@ -0,0 +1,93 @@
+use web_rust::generator::{Layout, generate_level};
+use web_rust::level::{LevelError, builtin_levels, parse_level};
+
+fn error(source: &str) -> LevelError
+{
+    parse_level(source).expect_err("level should not have parsed")
+}
+
+#[test]
+fn levels_read_back_the_same()
+{
+    let layouts = ["maze", "rooms", "pillars", "arena"].map(|name| Layout::from_name(name).unwrap());
+
+    let generated = layouts.into_iter().flat_map(|layout| (0..20).map(move |seed| generate_level(layout, 16, 10, seed)));
+
+    for level in builtin_levels().into_iter().chain(generated) {
+        let text = level.to_text();
+        let read = parse_level(&text).unwrap_or_else(|error| panic!("{}: {}", level.name, error));
+
+        assert_eq!(read.to_text(), text);
+        assert_eq!((read.start, read.direction, read.portals), (level.start, level.direction, level.portals));
+    }
+}
+
+#[test]
+fn errors_point_at_the_mistake()
+{
+    let header = error("name: Broken\nspeed: fast\n\n..S.\n");
+    assert_eq!((header.line, header.column), (2, 8));
+
+    let tile = error("direction: left\n\n....\n.S.x\n");
+    assert_eq!((tile.line, tile.column, tile.message.as_str()), (4, 4, "Unknown tile `x`"));
+
+    let short = error("direction: left\n\n....\n.S.\n");
+    assert_eq!((short.line, short.column), (4, 4));
+
+    let long = error("direction: left\n\n....\n.S...\n");
+    assert_eq!((long.line, long.column), (4, 5));
+}
+
+#[test]
+fn speeds_must_be_finite()
+{
+    assert_eq!(error("speed: inf\n\n..S.\n").line, 1);
+    assert_eq!(error("speedup: inf\n\n..S.\n").line, 1);
+    assert_eq!(error("fastest: inf\n\n..S.\n").line, 1);
+    assert_eq!(error("speed: NaN\n\n..S.\n").line, 1);
+}
+
+#[test]
+fn spaces_are_not_tiles()
+{
+    let space = error("direction: left\n\n....\n.S .\n");
+    assert_eq!((space.line, space.column), (4, 3));
+}
+
+#[test]
+fn there_is_exactly_one_start()
+{
+    let missing = error("name: Nowhere\n\n....\n....\n");
+    assert_eq!((missing.line, missing.column), (3, 1));
+
+    let second = error("direction: left\n\n.S..\n..S.\n");
+    assert_eq!((second.line, second.column, second.message.as_str()), (4, 3, "Level has more than one snake start"));
+}
+
+#[test]
+fn the_snake_starts_clear_of_walls_and_portals()
+{
+    // Going left, the body trails off to the right.
+    let wall = error("direction: left\n\n........\n..S.#...\n");
+    assert_eq!((wall.line, wall.column), (4, 5));
+
+    let portal = error("direction: up\n\nP.......\n........\n..S.....\n........\n..P.....\n");
+    assert_eq!((portal.line, portal.column), (7, 3));
+
+    // Wrapping around the edge...
+    let wrapped = error("edges: wrap\ndirection: left\n\n#......S\n");
+    assert_eq!((wrapped.line, wrapped.column), (4, 1));
+
+    // ...but not against walls, where the body is stacked on the board.
+    assert!(parse_level("edges: walls\ndirection: left\n\n#......S\n").is_ok());
+}
+
+#[test]
+fn portals_come_in_pairs()
+{
+    let unpaired = error("direction: left\n\n.P..P...\n..S....P\n");
+    assert_eq!((unpaired.line, unpaired.column, unpaired.message.as_str()), (4, 8, "Portal has no partner"));
+
+    let level = parse_level("direction: left\n\n.P..P...\n..S.....\n.....P.P\n").unwrap();
+    assert_eq!(level.portals, vec![((1, 2), (4, 2)), ((5, 0), (7, 0))]);
+}
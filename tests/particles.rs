@@ -0,0 +1,40 @@
+use web_rust::game::{Death, Events, new_game};
+use web_rust::level::builtin_levels;
+use web_rust::particles::{MAX_PARTICLES, Particles};
+
+#[test]
+fn eating_bursts_and_the_burst_dies_down()
+{
+    let ctx           = new_game(&builtin_levels()[0], 1, 3);
+    let mut particles = Particles::new(1);
+
+    particles.react(&ctx, &Events { ate: Some(0), ..Events::default() });
+    assert!(!particles.is_empty());
+
+    // Nothing moves while no time passes, like when the game is paused.
+    let before: Vec<_> = particles.particles.iter().map(|particle| particle.position).collect();
+    particles.update(0.);
+    let after: Vec<_> = particles.particles.iter().map(|particle| particle.position).collect();
+    assert_eq!(before, after);
+
+    for _ in 0..120 {
+        particles.update(1. / 60.);
+    }
+    assert!(particles.is_empty());
+}
+
+#[test]
+fn particles_are_capped()
+{
+    let mut ctx       = new_game(&builtin_levels()[0], 1, 3);
+    let mut particles = Particles::new(1);
+
+    // A snake over most of the board, dying over and over.
+    ctx.snakes[0].body = (0..16).flat_map(|x| (0..8).map(move |y| (x, y))).collect();
+
+    for _ in 0..10 {
+        particles.react(&ctx, &Events { died: vec![(0, Death::Body)], ..Events::default() });
+    }
+
+    assert_eq!(particles.particles.len(), MAX_PARTICLES);
+}
@@ -0,0 +1,278 @@
+use std::path::PathBuf;
+
+use web_rust::{BACKGROUND_COLOUR, Direction, SNAKE_COLOUR};
+use web_rust::game::{Context, new_game, step};
+use web_rust::level::builtin_levels;
+use web_rust::render::{Effect, Rasteriser, Renderer, ShaderError, letterbox};
+use web_rust::scene::{Hud, game_vertices, hud_vertices, text_vertices};
+use web_rust::text::{GLYPH_HEIGHT, GLYPH_WIDTH, SPACING, glyph};
+
+/// A tenth of the scene, so a cell comes out eight pixels wide.
+const WIDTH: usize  = 128;
+const HEIGHT: usize = 80;
+
+fn draw(ctx: &Context, progress: f32) -> Rasteriser
+{
+    let mut positions = vec![];
+    let mut colours   = vec![];
+    game_vertices(ctx, progress, &mut positions, &mut colours);
+
+    let mut rasteriser = Rasteriser::new(WIDTH, HEIGHT, (1280., 800.));
+    rasteriser.render(&positions, &colours).unwrap();
+    rasteriser
+}
+
+/// Compares the image with `tests/golden/<name>.ppm`. With `UPDATE_GOLDEN`
+/// set the golden image is written instead. When they differ the image
+/// drawn is left in `target/golden/`, to look at next to the golden one.
+fn assert_golden(name: &str, image: &Rasteriser)
+{
+    let ppm = ppm(image);
+    let golden = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/golden").join(format!("{}.ppm", name));
+
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        std::fs::create_dir_all(golden.parent().unwrap()).unwrap();
+        std::fs::write(&golden, &ppm).unwrap();
+        return
+    }
+
+    let expected = std::fs::read(&golden).unwrap_or_else(|_| panic!("{} is missing, run with UPDATE_GOLDEN=1", golden.display()));
+    if expected == ppm { return }
+
+    let actual = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("target/golden").join(format!("{}.ppm", name));
+    std::fs::create_dir_all(actual.parent().unwrap()).unwrap();
+    std::fs::write(&actual, &ppm).unwrap();
+
+    panic!("{} doesn't match {}", actual.display(), golden.display());
+}
+
+/// The image as a binary PPM, which is easy to write and most viewers open.
+fn ppm(image: &Rasteriser) -> Vec<u8>
+{
+    let mut ppm = format!("P6\n{} {}\n255\n", image.width, image.height).into_bytes();
+
+    for pixel in image.pixels.chunks_exact(4) {
+        ppm.extend_from_slice(&pixel[..3]);
+    }
+
+    ppm
+}
+
+/// Whether a pixel has the colour, give or take the rounding of interpolated colours.
+fn is(pixel: [u8; 4], colour: [f32; 3]) -> bool
+{
+    (0..3).all(|c| (pixel[c] as f32 - colour[c] * 255.).abs() <= 1.)
+}
+
+/// Steps the game with no input, to get the snake moving.
+fn stepped(level: usize, players: usize, ticks: usize) -> Context
+{
+    let mut ctx = new_game(&builtin_levels()[level], players, 5);
+
+    for _ in 0..ticks {
+        step(&mut ctx, &vec![None; players]);
+    }
+
+    ctx
+}
+
+#[test]
+fn open_field()
+{
+    assert_golden("open_field", &draw(&stepped(0, 1, 0), 1.));
+}
+
+#[test]
+fn half_a_step()
+{
+    assert_golden("half_a_step", &draw(&stepped(0, 1, 3), 0.5));
+}
+
+#[test]
+fn terrain()
+{
+    assert_golden("terrain", &draw(&stepped(5, 1, 2), 0.25));
+}
+
+#[test]
+fn portals()
+{
+    assert_golden("portals", &draw(&stepped(4, 1, 2), 0.75));
+}
+
+#[test]
+fn versus()
+{
+    assert_golden("versus", &draw(&stepped(0, 2, 2), 0.5));
+}
+
+#[test]
+fn head_split_across_the_edge()
+{
+    let mut ctx = new_game(&builtin_levels()[0], 1, 5);
+    ctx.apple   = None;
+
+    // Half way through going left off the board, and coming back on the right.
+    let snake       = &mut ctx.snakes[0];
+    snake.body      = vec![(15, 5), (0, 5), (1, 5)];
+    snake.previous  = vec![(0, 5), (1, 5), (2, 5)];
+    snake.headings  = vec![Direction::Left; 3];
+    snake.direction = Direction::Left;
+
+    let image = draw(&ctx, 0.5);
+    assert_golden("wraparound", &image);
+
+    // Row 5 from the bottom, with eight pixel cells.
+    let row = HEIGHT - 1 - 5 * 8 - 4;
+
+    // The half that's out already, on the right edge...
+    assert!(is(image.pixel(WIDTH - 1, row), SNAKE_COLOUR));
+    assert!(is(image.pixel(WIDTH - 4, row), SNAKE_COLOUR));
+    assert!(is(image.pixel(WIDTH - 5, row), BACKGROUND_COLOUR));
+
+    // ...and the body still on the left one.
+    assert!(is(image.pixel(0, row), SNAKE_COLOUR));
+}
+
+#[test]
+fn boxes_have_no_seams()
+{
+    let mut ctx = new_game(&builtin_levels()[0], 1, 5);
+    ctx.apple   = None;
+
+    // A snake over the whole bottom row, every box split along its diagonal.
+    let snake      = &mut ctx.snakes[0];
+    snake.body     = (0..16).rev().map(|x| (x, 0)).collect();
+    snake.previous = snake.body.clone();
+    snake.headings = vec![Direction::Right; 16];
+
+    let image = draw(&ctx, 1.);
+
+    for y in HEIGHT - 8..HEIGHT {
+        for x in 0..WIDTH {
+            assert!(is(image.pixel(x, y), SNAKE_COLOUR), "gap at {}, {}", x, y);
+        }
+    }
+
+    for x in 0..WIDTH {
+        assert!(is(image.pixel(x, HEIGHT - 9), BACKGROUND_COLOUR), "overdraw at {}", x);
+    }
+}
+
+#[test]
+fn text_follows_the_font()
+{
+    // A pixel of the font to a pixel of the image, from the bottom left of the tenth cell.
+    let mut positions = vec![];
+    let mut colours   = vec![];
+    text_vertices("S1", (100., 100.), 10., SNAKE_COLOUR, &mut positions, &mut colours);
+
+    let mut image = Rasteriser::new(WIDTH, HEIGHT, (1280., 800.));
+    image.render(&positions, &colours).unwrap();
+
+    for (i, c) in "S1".chars().enumerate() {
+        for (row, bits) in glyph(c).into_iter().enumerate() {
+            for column in 0..GLYPH_WIDTH + SPACING {
+                let lit   = column < GLYPH_WIDTH && bits & (1 << (GLYPH_WIDTH - 1 - column)) != 0;
+                let pixel = image.pixel(10 + i * (GLYPH_WIDTH + SPACING) + column, HEIGHT - 10 - GLYPH_HEIGHT + row);
+
+                assert_eq!(is(pixel, SNAKE_COLOUR), lit, "{:?} at {}, {}", c, column, row);
+            }
+        }
+    }
+}
+
+#[test]
+fn hud_says_when_paused()
+{
+    let ctx = stepped(0, 1, 0);
+
+    // A pixel of the HUD font to a pixel of the image.
+    let draw_hud = |hud: &Hud| {
+        let mut positions = vec![];
+        let mut colours   = vec![];
+        game_vertices(&ctx, 1., &mut positions, &mut colours);
+        hud_vertices(&ctx, hud, &mut positions, &mut colours);
+
+        let mut image = Rasteriser::new(320, 200, (1280., 800.));
+        image.render(&positions, &colours).unwrap();
+        image
+    };
+    let white     = [1., 1., 1.];
+    let in_middle = |image: &Rasteriser| (80..120).any(|y| (100..220).any(|x| is(image.pixel(x, y), white)));
+
+    let playing = draw_hud(&Hud { played: Some(65.), level: None, paused: false });
+    let paused  = draw_hud(&Hud { played: Some(65.), level: None, paused: true });
+
+    // The top row of the S starting off the score, in the top left corner.
+    for x in 6..9 {
+        assert!(is(playing.pixel(x, 4), white), "no score at {}", x);
+    }
+
+    assert!(!in_middle(&playing));
+    assert!(in_middle(&paused));
+}
+
+#[test]
+fn wide_images_are_letterboxed()
+{
+    let plain = draw(&stepped(0, 1, 3), 0.5);
+
+    // Half as wide again, so the board is in the middle with bars either side.
+    let mut wide = Rasteriser::new(WIDTH, HEIGHT, (1280., 800.));
+    wide.resize(WIDTH as u32 + 72, HEIGHT as u32);
+
+    let mut positions = vec![];
+    let mut colours   = vec![];
+    game_vertices(&stepped(0, 1, 3), 0.5, &mut positions, &mut colours);
+    wide.render(&positions, &colours).unwrap();
+
+    assert_eq!(wide.viewport, [36, 0, WIDTH, HEIGHT]);
+
+    for y in 0..HEIGHT {
+        for x in 0..36 {
+            assert_eq!(wide.pixel(x, y), [0; 4]);
+            assert_eq!(wide.pixel(WIDTH + 36 + x, y), [0; 4]);
+        }
+        for x in 0..WIDTH {
+            assert_eq!(wide.pixel(x + 36, y), plain.pixel(x, y));
+        }
+    }
+}
+
+#[test]
+fn letterbox_keeps_the_shape_of_the_board()
+{
+    // Too wide, bars on the sides.
+    assert_eq!(letterbox(2000., 800.), (360., 0., 1280., 800.));
+    // Too tall, bars above and below.
+    assert_eq!(letterbox(640., 1000.), (0., 300., 640., 400.));
+    // Just right, at twice the pixels.
+    assert_eq!(letterbox(2560., 1600.), (0., 0., 2560., 1600.));
+}
+
+#[test]
+fn effects_go_by_their_names()
+{
+    for effect in Effect::ALL {
+        assert_eq!(Effect::from_name(effect.name()), Some(effect));
+    }
+
+    assert_eq!(Effect::from_name("blur"), None);
+}
+
+#[test]
+fn shader_errors_say_where()
+{
+    let error = ShaderError::from_log("fragment", "ERROR: 0:12: 'colour' : undeclared identifier\nERROR: 0:13: '' : compilation terminated\n");
+
+    assert_eq!(error.stage, "fragment");
+    assert_eq!(error.line, Some(12));
+    assert_eq!(error.message, "'colour' : undeclared identifier");
+
+    // Logs in other shapes are passed on as they are.
+    let error = ShaderError::from_log("link", "Varyings don't match\n");
+    assert_eq!((error.line, error.message.as_str()), (None, "Varyings don't match"));
+
+    assert!(Rasteriser::new(1, 1, (1., 1.)).load_shaders("", "").is_err());
+}
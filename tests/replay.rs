@@ -0,0 +1,41 @@
+use web_rust::bot::{Autopilot, Strategy};
+use web_rust::game::{new_game, step};
+use web_rust::level::builtin_levels;
+use web_rust::net::state_hash;
+use web_rust::replay::Replay;
+
+#[test]
+fn replay_plays_the_same_game()
+{
+    let level = &builtin_levels()[1];
+
+    let mut ctx       = new_game(level, 1, 11);
+    let mut recording = Replay::new(11);
+
+    for _ in 0..500 {
+        let input = Autopilot.next_move(&ctx, 0);
+        recording.record(&ctx, input);
+
+        if !step(&mut ctx, &[Some(input)]).died.is_empty() { break }
+    }
+
+    let mut replay = Replay::decode(&recording.encode()).unwrap();
+    assert_eq!(replay, recording);
+
+    let mut again = new_game(level, 1, replay.seed);
+
+    for _ in 0..500 {
+        let input = replay.next_move(&again, 0);
+        if !step(&mut again, &[Some(input)]).died.is_empty() { break }
+    }
+
+    assert_eq!(state_hash(&again), state_hash(&ctx));
+}
+
+#[test]
+fn turns_out_of_order_are_rejected()
+{
+    assert!(Replay::decode("seed 1\n3 up\n5 left\n").is_some());
+    assert!(Replay::decode("seed 1\n5 up\n3 left\n").is_none());
+    assert!(Replay::decode("3 up\n").is_none());
+}
@@ -0,0 +1,100 @@
+use web_rust::Direction;
+use web_rust::game::{new_game, step};
+use web_rust::level::builtin_levels;
+use web_rust::net::{MemoryTransport, state_hash};
+use web_rust::rollback::Rollback;
+
+const SEED: u64 = 7;
+
+/// What each player presses on a given tick. Both turn every few ticks,
+/// at different times, so the other side keeps guessing wrong.
+fn script(player: usize, tick: usize) -> Option<Direction>
+{
+    let turns = [Direction::Up, Direction::Left, Direction::Down, Direction::Right];
+
+    match player {
+        0 => Some(turns[(tick / 5) % 4]),
+        _ => Some(turns[(tick / 7 + 1) % 4])
+    }
+}
+
+/// Plays both sides against each other until the game is over on both,
+/// or `ticks` have gone by.
+fn play(latency: usize, ticks: usize) -> [Rollback; 2]
+{
+    let level = &builtin_levels()[0];
+
+    let mut sides  = [Rollback::new(0, level, SEED), Rollback::new(1, level, SEED)];
+    let mut wires  = [MemoryTransport::new(latency), MemoryTransport::new(latency)];
+
+    for now in 0..ticks {
+        for (player, side) in sides.iter_mut().enumerate() {
+            side.advance(script(player, side.tick));
+
+            for message in side.messages() {
+                wires[player].send(now, message);
+            }
+        }
+
+        for (player, side) in sides.iter_mut().enumerate() {
+            for message in wires[1 - player].receive(now) {
+                side.receive(message);
+            }
+        }
+
+        if sides.iter().all(|side| side.finished()) { break }
+    }
+
+    sides
+}
+
+#[test]
+fn rollback_matches_a_game_played_in_one_place()
+{
+    let sides = play(4, 2000);
+
+    let mut reference = new_game(&builtin_levels()[0], 2, SEED);
+    let mut end       = None;
+
+    for tick in 0..2000 {
+        let events = step(&mut reference, &[script(0, tick), script(1, tick)]);
+        if !events.died.is_empty() {
+            end = Some(tick);
+            break
+        }
+    }
+
+    assert!(end.is_some(), "the scripted game should end");
+
+    for side in &sides {
+        assert!(side.finished());
+        assert_eq!(side.desync(), None);
+        assert!(side.rollbacks > 0);
+        assert_eq!(state_hash(&side.ctx), state_hash(&reference));
+    }
+}
+
+#[test]
+fn corrupted_game_is_reported_as_a_desync()
+{
+    let level = &builtin_levels()[0];
+
+    let mut sides = [Rollback::new(0, level, SEED), Rollback::new(1, level, SEED)];
+    sides[1].ctx.snakes[0].score += 1;
+
+    for _ in 0..100 {
+        let messages: Vec<_> = sides.iter_mut()
+            .enumerate()
+            .map(|(player, side)| {
+                side.advance(script(player, side.tick));
+                side.messages()
+            })
+            .collect();
+
+        for (player, messages) in messages.into_iter().enumerate() {
+            for message in messages { sides[1 - player].receive(message); }
+        }
+    }
+
+    assert!(sides.iter().all(|side| side.desync().is_some()));
+}
@@ -0,0 +1,82 @@
+use web_rust::bot::{Autopilot, Strategy};
+use web_rust::game::{Context, new_game, step};
+use web_rust::level::builtin_levels;
+use web_rust::spectate::{Broadcaster, Frame, KEYFRAME_INTERVAL, Spectator};
+
+/// Plays a game with the bot, handing every frame to `each`.
+fn broadcast(ticks: usize, mut each: impl FnMut(&Context, Frame))
+{
+    let level = &builtin_levels()[1];
+
+    let mut ctx         = new_game(level, 1, 7);
+    let mut broadcaster = Broadcaster::default();
+
+    for _ in 0..ticks {
+        let input = Autopilot.next_move(&ctx, 0);
+        if !step(&mut ctx, &[Some(input)]).died.is_empty() { break }
+
+        let frame = broadcaster.frame(&ctx, level);
+        each(&ctx, frame);
+    }
+}
+
+fn same_game(spectated: &Context, played: &Context) -> bool
+{
+    spectated.ticks == played.ticks
+        && spectated.apple == played.apple
+        && spectated.snakes.iter().zip(&played.snakes).all(|(one, two)| {
+            one.body == two.body && one.headings == two.headings && one.direction == two.direction && one.score == two.score
+        })
+}
+
+#[test]
+fn frames_read_back_the_same()
+{
+    let mut keyframes = 0;
+
+    broadcast(2 * KEYFRAME_INTERVAL + 1, |_, frame| {
+        let text = frame.encode();
+        let read = Frame::decode(&text).unwrap();
+
+        assert_eq!(read.encode(), text);
+        assert_eq!((read.sequence, read.ticks, read.apple, read.snakes), (frame.sequence, frame.ticks, frame.apple, frame.snakes));
+
+        if let Some(level) = read.level {
+            assert_eq!(level.to_text(), frame.level.unwrap().to_text());
+            keyframes += 1;
+        }
+    });
+
+    assert_eq!(keyframes, 3);
+
+    assert!(Frame::decode("delta 1 2").is_none());
+    assert!(Frame::decode("key 1 2 200 = /0/0").is_none());
+}
+
+#[test]
+fn spectators_follow_the_game()
+{
+    let mut spectator = Spectator::default();
+
+    broadcast(2 * KEYFRAME_INTERVAL + 1, |ctx, frame| {
+        assert!(spectator.receive(&Frame::decode(&frame.encode()).unwrap()));
+        assert!(same_game(spectator.ctx.as_ref().unwrap(), ctx), "lost track on tick {}", ctx.ticks);
+    });
+}
+
+#[test]
+fn missing_a_frame_waits_for_the_next_keyframe()
+{
+    let mut spectator = Spectator::default();
+
+    broadcast(KEYFRAME_INTERVAL + 1, |ctx, frame| {
+        match frame.sequence {
+            10 => (),
+            11..KEYFRAME_INTERVAL => assert!(!spectator.receive(&frame)),
+            _ => {
+                assert!(spectator.receive(&frame));
+                assert!(same_game(spectator.ctx.as_ref().unwrap(), ctx));
+            }
+        }
+    });
+}
@@ -0,0 +1,52 @@
+use web_rust::Direction;
+use web_rust::bot::{Autopilot, Strategy};
+use web_rust::game::{Context, Death};
+use web_rust::level::builtin_levels;
+use web_rust::tournament::tournament;
+
+/// Never turns.
+struct Straight;
+
+impl Strategy for Straight
+{
+    fn name(&self) -> String
+    {
+        String::from("straight")
+    }
+
+    fn next_move(&mut self, ctx: &Context, player: usize) -> Direction
+    {
+        ctx.snakes[player].direction
+    }
+}
+
+#[test]
+fn every_death_is_counted()
+{
+    // The portals level has walls, and nothing between the start and the left one.
+    let level = builtin_levels().into_iter().find(|level| level.name == "Portals").unwrap();
+
+    let mut strategies: Vec<Box<dyn Strategy>> = vec![Box::new(Straight)];
+    let report = &tournament(&mut strategies, &level, 5, 1000)[0];
+
+    assert_eq!(report.strategy, "straight");
+    assert_eq!(report.games, 5);
+    assert_eq!(report.survived, 0);
+    assert_eq!(report.deaths, vec![(Death::Wall, 5)]);
+    assert_eq!(report.median_ticks, level.start.0 as f64);
+}
+
+/// The autopilot can clear the open field every time. If it suddenly can't,
+/// a change to the rules most likely broke moving or colliding.
+#[test]
+fn autopilot_clears_the_open_field()
+{
+    let level = &builtin_levels()[0];
+
+    let mut strategies: Vec<Box<dyn Strategy>> = vec![Box::new(Autopilot)];
+    let report = &tournament(&mut strategies, level, 5, 10_000)[0];
+
+    assert_eq!(report.survived, 5);
+    assert!(report.deaths.is_empty());
+    assert!(report.mean_score > 100.);
+}